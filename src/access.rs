@@ -0,0 +1,123 @@
+use std::any::TypeId;
+
+/// Components and resources a system reads and writes, used by [crate::Systems] to decide which systems can run at the same time.
+///
+/// Function systems get it from their [crate::SystemParam]s, other systems declare it with [crate::System::access()].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    read_components: Vec<TypeId>,
+    write_components: Vec<TypeId>,
+    read_resources: Vec<TypeId>,
+    write_resources: Vec<TypeId>,
+    read_all: bool,  // reads every component and resource
+    write_all: bool, // may write any component or resource, like a `&World` parameter
+    commands: bool, // reserves entities, which has to happen in the same order as when running serially
+    main_thread: bool, // uses non-Send resources
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read_component<T: 'static>(&mut self) -> &mut Self {
+        self.read_components.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn write_component<T: 'static>(&mut self) -> &mut Self {
+        self.write_components.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn read_resource<T: 'static>(&mut self) -> &mut Self {
+        self.read_resources.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn write_resource<T: 'static>(&mut self) -> &mut Self {
+        self.write_resources.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn read_all(&mut self) -> &mut Self {
+        self.read_all = true;
+        self
+    }
+
+    /// Marks the system as possibly writing any component or resource, it never runs at the same time as other systems.
+    pub fn write_all(&mut self) -> &mut Self {
+        self.write_all = true;
+        self
+    }
+
+    /// Marks the system as recording [crate::Commands].
+    pub fn commands(&mut self) -> &mut Self {
+        self.commands = true;
+        self
+    }
+
+    /// Marks the system as using non-Send resources, such systems run alone on the thread calling [crate::Systems::update()].
+    pub fn main_thread(&mut self) -> &mut Self {
+        self.main_thread = true;
+        self
+    }
+
+    pub(crate) fn is_main_thread(&self) -> bool {
+        self.main_thread
+    }
+
+    /// Returns true if running both systems at the same time could give a different result than running them one after another.
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        fn overlap(first: &[TypeId], second: &[TypeId]) -> bool {
+            first.iter().any(|type_id| second.contains(type_id))
+        }
+        fn writes_read_by(writer: &Access, reader: &Access) -> bool {
+            let writes = !writer.write_components.is_empty() || !writer.write_resources.is_empty();
+            (reader.read_all && writes)
+                || overlap(&writer.write_components, &reader.read_components)
+                || overlap(&writer.write_components, &reader.write_components)
+                || overlap(&writer.write_resources, &reader.read_resources)
+                || overlap(&writer.write_resources, &reader.write_resources)
+        }
+        self.write_all
+            || other.write_all
+            || (self.commands && other.commands)
+            || writes_read_by(self, other) || writes_read_by(other, self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Access;
+
+    #[test]
+    fn conflicts() {
+        let mut reads = Access::new();
+        reads.read_component::<u32>().read_resource::<f32>();
+        let mut other_reads = Access::new();
+        other_reads.read_component::<u32>().write_component::<u8>();
+        let mut writes = Access::new();
+        writes.write_component::<u32>();
+        let mut world = Access::new();
+        world.read_all();
+
+        assert!(!reads.conflicts_with(&other_reads));
+        assert!(reads.conflicts_with(&writes));
+        assert!(writes.conflicts_with(&other_reads));
+        assert!(writes.conflicts_with(&writes));
+        assert!(world.conflicts_with(&writes));
+        assert!(!world.conflicts_with(&reads));
+
+        let mut everything = Access::new();
+        everything.write_all();
+        assert!(everything.conflicts_with(&reads));
+        assert!(reads.conflicts_with(&everything));
+        assert!(everything.conflicts_with(&Access::new()));
+
+        let mut commands = Access::new();
+        commands.commands();
+        assert!(commands.conflicts_with(&commands.clone()));
+        assert!(!commands.conflicts_with(&reads));
+    }
+}
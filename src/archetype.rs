@@ -0,0 +1,228 @@
+use std::{any::Any, collections::HashMap, slice};
+
+use crate::{
+    bit_set::BitSet,
+    column::{AnyColumn, Column},
+    component::Components,
+    Entity,
+};
+
+/// Where the components of an entity are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EntityLocation {
+    pub archetype: usize,
+    pub row: usize,
+}
+
+/// Table of all entities sharing the same set of components. Every component has its own dense column
+/// and row `n` of each column belongs to `entities[n]`.
+pub struct Archetype {
+    components: BitSet,
+    entities: Vec<Entity>,
+    columns: HashMap<usize, Box<dyn AnyColumn>>,
+}
+
+impl Archetype {
+    fn new(components: BitSet, columns: HashMap<usize, Box<dyn AnyColumn>>) -> Self {
+        Self {
+            components,
+            entities: vec![],
+            columns,
+        }
+    }
+
+    pub(crate) fn components(&self) -> &BitSet {
+        &self.components
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub(crate) fn check_change_ticks(&mut self, change_tick: u32) {
+        for column in self.columns.values_mut() {
+            column.check_change_ticks(change_tick);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub(crate) fn column(&self, component_id: usize) -> Option<&dyn AnyColumn> {
+        self.columns.get(&component_id).map(|column| column.as_ref())
+    }
+
+    pub fn typed_column<T: Any>(&self, component_id: usize) -> Option<&Column<T>> {
+        self.column(component_id)?.as_any().downcast_ref()
+    }
+
+    pub fn typed_column_mut<T: Any>(&mut self, component_id: usize) -> Option<&mut Column<T>> {
+        self.columns
+            .get_mut(&component_id)?
+            .as_any_mut()
+            .downcast_mut()
+    }
+
+    /// Adds an entity without any column data, every column has to be pushed to afterwards.
+    pub fn push_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
+    }
+
+    /// Makes room for `additional` more entities in every column.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+        for column in self.columns.values_mut() {
+            column.reserve(additional);
+        }
+    }
+
+    /// Drops every component of the row. Returns the entity which was moved into the freed row.
+    pub fn remove_row(&mut self, row: usize) -> Option<Entity> {
+        for column in self.columns.values_mut() {
+            column.swap_remove(row);
+        }
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+
+    /// Moves the row into `target`. Components missing from `target` are dropped, except the one with the id
+    /// of `taken` which is pushed into its column instead. Columns of `target` which this archetype does not have
+    /// are left for the caller to fill. Returns the entity which was moved into the freed row.
+    pub(crate) fn move_row(
+        &mut self,
+        row: usize,
+        target: &mut Archetype,
+        mut taken: Option<(usize, &mut dyn AnyColumn)>,
+    ) -> Option<Entity> {
+        for (component_id, column) in self.columns.iter_mut() {
+            match (target.columns.get_mut(component_id), &mut taken) {
+                (Some(target_column), _) => column.move_row(row, target_column.as_mut()),
+                (None, Some((taken_id, taken_column))) if taken_id == component_id => {
+                    column.move_row(row, *taken_column)
+                }
+                (None, _) => column.swap_remove(row),
+            }
+        }
+        target.entities.push(self.entities.swap_remove(row));
+        self.entities.get(row).copied()
+    }
+}
+
+/// All archetypes of a world. The first one is always the archetype without components.
+pub(crate) struct Archetypes {
+    archetypes: Vec<Archetype>,
+    ids: HashMap<BitSet, usize>,
+}
+
+impl Default for Archetypes {
+    fn default() -> Self {
+        let empty = Archetype::new(BitSet::new(), HashMap::new());
+        Self {
+            archetypes: vec![empty],
+            ids: HashMap::from([(BitSet::new(), Self::EMPTY)]),
+        }
+    }
+}
+
+impl Archetypes {
+    pub const EMPTY: usize = 0;
+
+    /// Returns the archetype with exactly the given components, creating it if needed.
+    pub fn get_or_insert(&mut self, components: BitSet, registry: &Components) -> usize {
+        if let Some(id) = self.ids.get(&components) {
+            return *id;
+        }
+        let columns = (0..registry.len())
+            .filter(|component_id| components.contains(*component_id))
+            .map(|component_id| (component_id, (registry.info(component_id).new_column)()))
+            .collect();
+        let id = self.archetypes.len();
+        self.archetypes.push(Archetype::new(components.clone(), columns));
+        self.ids.insert(components, id);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> &Archetype {
+        &self.archetypes[id]
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> &mut Archetype {
+        &mut self.archetypes[id]
+    }
+
+    /// Borrows two different archetypes mutably at once.
+    pub fn get_two_mut(&mut self, first: usize, second: usize) -> (&mut Archetype, &mut Archetype) {
+        assert_ne!(first, second);
+        if first < second {
+            let (left, right) = self.archetypes.split_at_mut(second);
+            (&mut left[first], &mut right[0])
+        } else {
+            let (left, right) = self.archetypes.split_at_mut(first);
+            (&mut right[0], &mut left[second])
+        }
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Archetype> {
+        self.archetypes.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Archetype> {
+        self.archetypes.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        archetype::Archetypes,
+        bit_set::BitSet,
+        component::{Components, StorageType},
+        Component, Entity,
+    };
+
+    #[derive(Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Speed(f32);
+
+    #[test]
+    fn move_row_keeps_shared_columns() {
+        let mut components = Components::default();
+        let health = components.register::<Health>(StorageType::Table);
+        let speed = components.register::<Speed>(StorageType::Table);
+        let mut archetypes = Archetypes::default();
+
+        let mut both = BitSet::new();
+        both.insert(health);
+        both.insert(speed);
+        let mut only_health = BitSet::new();
+        only_health.insert(health);
+        let from = archetypes.get_or_insert(both.clone(), &components);
+        let to = archetypes.get_or_insert(only_health, &components);
+        assert_eq!(archetypes.get_or_insert(both, &components), from);
+
+        let archetype = archetypes.get_mut(from);
+        for index in 0..3 {
+            archetype.push_entity(Entity::new(index, 0));
+            archetype.typed_column_mut::<Health>(health).unwrap().push(Health(index as u32), 0);
+            archetype.typed_column_mut::<Speed>(speed).unwrap().push(Speed(index as f32), 0);
+        }
+
+        let (from_archetype, to_archetype) = archetypes.get_two_mut(from, to);
+        let swapped = from_archetype.move_row(0, to_archetype, None);
+        assert_eq!(swapped, Some(Entity::new(2, 0)));
+
+        let from_archetype = archetypes.get(from);
+        assert_eq!(from_archetype.entities(), &[Entity::new(2, 0), Entity::new(1, 0)]);
+        let speeds = from_archetype.typed_column::<Speed>(speed).unwrap();
+        assert_eq!(speeds.get(0).unwrap().borrow().0, 2.0);
+
+        let to_archetype = archetypes.get(to);
+        assert_eq!(to_archetype.entities(), &[Entity::new(0, 0)]);
+        let healths = to_archetype.typed_column::<Health>(health).unwrap();
+        assert_eq!(healths.get(0).unwrap().borrow().0, 0);
+        assert!(to_archetype.column(speed).is_none());
+    }
+}
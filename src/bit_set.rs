@@ -0,0 +1,126 @@
+const BLOCK_BITS: usize = u128::BITS as usize;
+
+/// Growable set of component bits. The first 128 bits live inline so worlds with
+/// up to 128 component types never allocate, every bit after that spills into `overflow`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct BitSet {
+    inline: u128,
+    overflow: Vec<u128>, // never ends with an empty block so equal sets compare and hash equal
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, bit: usize) {
+        if bit < BLOCK_BITS {
+            self.inline |= 1 << bit;
+            return;
+        }
+        let (block, offset) = Self::position(bit);
+        if self.overflow.len() <= block {
+            self.overflow.resize(block + 1, 0);
+        }
+        self.overflow[block] |= 1 << offset;
+    }
+
+    pub fn remove(&mut self, bit: usize) {
+        if bit < BLOCK_BITS {
+            self.inline &= !(1 << bit);
+            return;
+        }
+        let (block, offset) = Self::position(bit);
+        if let Some(bits) = self.overflow.get_mut(block) {
+            *bits &= !(1 << offset);
+        }
+        while self.overflow.last() == Some(&0) {
+            self.overflow.pop();
+        }
+    }
+
+    pub fn contains(&self, bit: usize) -> bool {
+        if bit < BLOCK_BITS {
+            return self.inline & (1 << bit) != 0;
+        }
+        let (block, offset) = Self::position(bit);
+        self.overflow
+            .get(block)
+            .is_some_and(|bits| bits & (1 << offset) != 0)
+    }
+
+    /// Returns true if every bit of `other` is also set in `self`.
+    pub fn contains_all(&self, other: &BitSet) -> bool {
+        if self.inline & other.inline != other.inline {
+            return false;
+        }
+        if other.overflow.len() > self.overflow.len() {
+            return false;
+        }
+        self.overflow
+            .iter()
+            .zip(&other.overflow)
+            .all(|(bits, other_bits)| bits & other_bits == *other_bits)
+    }
+
+    fn position(bit: usize) -> (usize, usize) {
+        (bit / BLOCK_BITS - 1, bit % BLOCK_BITS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bit_set::BitSet;
+
+    #[test]
+    fn insert_and_remove_bits() {
+        let mut set = BitSet::new();
+        set.insert(0);
+        set.insert(127);
+        set.insert(128);
+        set.insert(1000);
+
+        assert!(set.contains(0));
+        assert!(set.contains(127));
+        assert!(set.contains(128));
+        assert!(set.contains(1000));
+        assert!(!set.contains(1));
+        assert!(!set.contains(999));
+        assert!(!set.contains(5000));
+
+        set.remove(1000);
+        set.remove(0);
+        assert!(!set.contains(1000));
+        assert!(!set.contains(0));
+        assert_eq!(set.overflow.len(), 1);
+    }
+
+    #[test]
+    fn contains_all_across_blocks() {
+        let mut set = BitSet::new();
+        let mut subset = BitSet::new();
+        for bit in [3, 130, 300] {
+            set.insert(bit);
+            subset.insert(bit);
+        }
+        set.insert(4);
+        assert!(set.contains_all(&subset));
+        assert!(!subset.contains_all(&set));
+
+        subset.insert(600);
+        assert!(!set.contains_all(&subset));
+        assert!(set.contains_all(&BitSet::new()));
+    }
+
+    #[test]
+    fn removed_bits_compare_equal() {
+        let mut set = BitSet::new();
+        set.insert(5);
+        set.insert(400);
+        set.remove(400);
+
+        let mut other = BitSet::new();
+        other.insert(5);
+        assert_eq!(set, other);
+    }
+}
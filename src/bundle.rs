@@ -0,0 +1,139 @@
+use std::any::TypeId;
+
+use crate::{archetype::EntityLocation, Component, Entity, World};
+
+/// Group of components inserted or removed together with [World::spawn()], [World::add_bundle()] and [World::remove_bundle()].
+///
+/// Implemented for every [Component], for tuples of bundles and for structs with `#[derive(Bundle)]`,
+/// whose fields have to be bundles themselves.
+///
+/// Example:
+/// ```
+/// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Speed(u32);
+/// #[derive(Component)]
+/// struct Player;
+///
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     health: Health,
+///     speed: Speed,
+///     player: Player,
+/// }
+///
+/// let mut world = World::new();
+/// let enemy = world.spawn((Health(50), Speed(5)));
+/// let player = world.spawn(PlayerBundle {
+///     health: Health(100),
+///     speed: Speed(10),
+///     player: Player,
+/// });
+/// world.remove_bundle::<(Speed, Player)>(player).unwrap();
+///
+/// assert_eq!(world.query::<&Speed>().run().len(), 1);
+/// assert_eq!(world.query::<&Health>().get(player).unwrap().0, 100);
+/// assert_eq!(world.query::<&Health>().get(enemy).unwrap().0, 50);
+/// ```
+pub trait Bundle: Send + Sync + 'static {
+    /// Registers the components of the bundle and pushes their ids in order.
+    #[doc(hidden)]
+    fn register_components(world: &mut World, component_ids: &mut Vec<usize>);
+
+    /// Pushes ids of the components which are already registered, in order.
+    #[doc(hidden)]
+    fn registered_components(world: &World, component_ids: &mut Vec<usize>);
+
+    #[doc(hidden)]
+    fn write(self, writer: &mut BundleWriter<'_>);
+}
+
+/// Writes components of a [Bundle] into storages prepared by the [World], only the world can create it.
+#[doc(hidden)]
+pub struct BundleWriter<'w> {
+    world: &'w mut World,
+    entity: Entity,
+    location: EntityLocation, // already in the archetype with columns for every table component of the bundle
+}
+
+impl<'w> BundleWriter<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity, location: EntityLocation) -> Self {
+        Self {
+            world,
+            entity,
+            location,
+        }
+    }
+
+    /// Inserts the component or replaces the one the entity already has.
+    pub fn write<T: Component>(&mut self, value: T) {
+        let world = &mut *self.world;
+        let component_id = world
+            .components
+            .id(TypeId::of::<T>())
+            .expect("bundle components are registered before they are written");
+
+        if let Some(sparse_set) = world.sparse_sets.get_mut(&component_id) {
+            sparse_set.insert(self.entity, value, world.change_tick);
+            return;
+        }
+
+        let column = world
+            .archetypes
+            .get_mut(self.location.archetype)
+            .typed_column_mut::<T>(component_id)
+            .expect("target archetype has a column for every component of the bundle");
+        match self.location.row < column.len() {
+            true => column.replace(self.location.row, value, world.change_tick),
+            false => column.push(value, world.change_tick),
+        }
+    }
+}
+
+impl<T: Component> Bundle for T {
+    fn register_components(world: &mut World, component_ids: &mut Vec<usize>) {
+        component_ids.push(world.init_component::<T>(T::STORAGE));
+    }
+
+    fn registered_components(world: &World, component_ids: &mut Vec<usize>) {
+        component_ids.extend(world.components.id(TypeId::of::<T>()));
+    }
+
+    fn write(self, writer: &mut BundleWriter<'_>) {
+        writer.write(self);
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+            fn register_components(world: &mut World, component_ids: &mut Vec<usize>) {
+                $($name::register_components(world, component_ids);)*
+            }
+
+            fn registered_components(world: &World, component_ids: &mut Vec<usize>) {
+                $($name::registered_components(world, component_ids);)*
+            }
+
+            fn write(self, writer: &mut BundleWriter<'_>) {
+                let ($($name,)*) = self;
+                $($name.write(writer);)*
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!();
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J);
@@ -0,0 +1,298 @@
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Thread safe counterpart of [std::cell::RefCell] storing resources.
+///
+/// Borrows never block: like with a `RefCell`, [AtomicRefCell::borrow()] panics if the value is borrowed mutably
+/// and [AtomicRefCell::borrow_mut()] panics if it is borrowed at all.
+#[derive(Debug, Default)]
+pub struct AtomicRefCell<T: ?Sized> {
+    borrow: BorrowFlag,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the value is only reached through borrows checked by the flag, like with a `RwLock`
+unsafe impl<T: ?Sized + Send + Sync> Sync for AtomicRefCell<T> {}
+
+impl<T> AtomicRefCell<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            borrow: BorrowFlag::default(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AtomicRefCell<T> {
+    /// Returns None if the value is borrowed mutably.
+    pub fn try_borrow(&self) -> Option<AtomicRef<'_, T>> {
+        self.cell().try_borrow()
+    }
+
+    /// Returns None if the value is borrowed.
+    pub fn try_borrow_mut(&self) -> Option<AtomicRefMut<'_, T>> {
+        self.cell().try_borrow_mut()
+    }
+
+    pub fn borrow(&self) -> AtomicRef<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    pub fn borrow_mut(&self) -> AtomicRefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    fn cell(&self) -> ComponentCell<'_, T> {
+        ComponentCell::new(&self.borrow, None, &self.value)
+    }
+}
+
+/// A single component inside a column, borrowed at runtime on its own like an [AtomicRefCell].
+///
+/// Returned by [crate::DynamicQuery::run()], typed queries borrow through it as well.
+pub struct ComponentCell<'a, T: ?Sized> {
+    borrow: &'a BorrowFlag,
+    column: Option<&'a BorrowFlag>, // borrow of the whole column, see column::ColumnBorrow
+    value: &'a UnsafeCell<T>,
+}
+
+impl<T: ?Sized> Clone for ComponentCell<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for ComponentCell<'_, T> {}
+
+impl<'a, T: ?Sized> ComponentCell<'a, T> {
+    /// `borrow` has to guard every access to `value` which does not go through `column`.
+    pub(crate) fn new(borrow: &'a BorrowFlag, column: Option<&'a BorrowFlag>, value: &'a UnsafeCell<T>) -> Self {
+        Self { borrow, column, value }
+    }
+
+    /// Returns None if the value is borrowed mutably.
+    pub fn try_borrow(&self) -> Option<AtomicRef<'a, T>> {
+        if !self.borrow.try_borrow() {
+            return None;
+        }
+        // checked after taking the borrow, the column does it the other way around, see BorrowFlag
+        if self.column.is_some_and(BorrowFlag::is_exclusive) {
+            self.borrow.release();
+            return None;
+        }
+        Some(AtomicRef {
+            // SAFETY: the flags only allow shared borrows now
+            value: unsafe { &*self.value.get() },
+            borrow: Some(self.borrow),
+        })
+    }
+
+    /// Returns None if the value is borrowed.
+    pub fn try_borrow_mut(&self) -> Option<AtomicRefMut<'a, T>> {
+        if !self.borrow.try_borrow_mut() {
+            return None;
+        }
+        if self.column.is_some_and(|column| !column.is_free()) {
+            self.borrow.release_mut();
+            return None;
+        }
+        Some(AtomicRefMut {
+            // SAFETY: the flags exclude every other borrow
+            value: unsafe { &mut *self.value.get() },
+            borrow: Some(self.borrow),
+        })
+    }
+
+    pub fn borrow(&self) -> AtomicRef<'a, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    pub fn borrow_mut(&self) -> AtomicRefMut<'a, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+
+    /// Borrows the value without touching its flag.
+    ///
+    /// # Safety
+    /// The whole column has to be borrowed while the returned value lives.
+    pub(crate) unsafe fn borrow_unchecked(&self) -> AtomicRef<'a, T> {
+        AtomicRef {
+            value: unsafe { &*self.value.get() },
+            borrow: None,
+        }
+    }
+
+    /// Borrows the value mutably without touching its flag.
+    ///
+    /// # Safety
+    /// The whole column has to be borrowed mutably while the returned value lives, and the value can not be
+    /// borrowed this way twice at the same time.
+    pub(crate) unsafe fn borrow_mut_unchecked(&self) -> AtomicRefMut<'a, T> {
+        AtomicRefMut {
+            value: unsafe { &mut *self.value.get() },
+            borrow: None,
+        }
+    }
+}
+
+const EXCLUSIVE: usize = usize::MAX;
+
+/// Borrow state of a single value or of a whole column: the number of shared borrows or [EXCLUSIVE].
+///
+/// A column has one flag per row and one for the whole column. Both sides take their own flag first and then check
+/// the other one, with sequentially consistent ordering at least one of two conflicting borrows sees the other and fails.
+#[derive(Debug, Default)]
+pub(crate) struct BorrowFlag(AtomicUsize);
+
+impl BorrowFlag {
+    /// Returns false if the flag is borrowed mutably.
+    pub fn try_borrow(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_add(1).filter(|count| *count < EXCLUSIVE)
+            })
+            .is_ok()
+    }
+
+    /// Returns false if the flag is borrowed.
+    pub fn try_borrow_mut(&self) -> bool {
+        self.0
+            .compare_exchange(0, EXCLUSIVE, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    pub fn release(&self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn release_mut(&self) {
+        self.0.store(0, Ordering::SeqCst);
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == EXCLUSIVE
+    }
+}
+
+/// Shared borrow of an [AtomicRefCell] or a [ComponentCell], returned by queries for `&T`.
+#[derive(Debug)]
+pub struct AtomicRef<'a, T: ?Sized> {
+    value: &'a T,
+    borrow: Option<&'a BorrowFlag>, // None if the whole column is borrowed instead
+}
+
+impl<T: ?Sized> Deref for AtomicRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicRef<'_, T> {
+    fn drop(&mut self) {
+        if let Some(borrow) = self.borrow {
+            borrow.release();
+        }
+    }
+}
+
+/// Exclusive borrow of an [AtomicRefCell] or a [ComponentCell], returned by queries for `&mut T`.
+#[derive(Debug)]
+pub struct AtomicRefMut<'a, T: ?Sized> {
+    value: &'a mut T,
+    borrow: Option<&'a BorrowFlag>, // None if the whole column is borrowed instead
+}
+
+impl<T: ?Sized> Deref for AtomicRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for AtomicRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicRefMut<'_, T> {
+    fn drop(&mut self) {
+        if let Some(borrow) = self.borrow {
+            borrow.release_mut();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{any::Any, cell::UnsafeCell};
+
+    use crate::{cell::BorrowFlag, AtomicRefCell, ComponentCell};
+
+    #[test]
+    fn borrows_are_checked() {
+        let cell = AtomicRefCell::new(5_u32);
+        let first = cell.borrow();
+        let second = cell.borrow();
+        assert!(cell.try_borrow_mut().is_none());
+        assert_eq!(*first + *second, 10);
+        drop((first, second));
+
+        let mut borrowed = cell.borrow_mut();
+        *borrowed += 1;
+        assert!(cell.try_borrow().is_none());
+        assert!(cell.try_borrow_mut().is_none());
+        drop(borrowed);
+        assert_eq!(cell.into_inner(), 6);
+    }
+
+    #[test]
+    fn unsized_cell() {
+        let cell = AtomicRefCell::new(5_u32);
+        let any: &AtomicRefCell<dyn Any> = &cell;
+        assert_eq!(any.borrow().downcast_ref::<u32>(), Some(&5));
+    }
+
+    #[test]
+    fn cells_check_the_column_borrow() {
+        let column = BorrowFlag::default();
+        let rows = [BorrowFlag::default(), BorrowFlag::default()];
+        let values = [UnsafeCell::new(1_u32), UnsafeCell::new(2)];
+        let first = ComponentCell::new(&rows[0], Some(&column), &values[0]);
+        let second = ComponentCell::new(&rows[1], Some(&column), &values[1]);
+
+        // rows are independent
+        let mut first_mut = first.borrow_mut();
+        *first_mut += *second.borrow();
+        assert!(first.try_borrow().is_none());
+        drop(first_mut);
+
+        assert!(column.try_borrow());
+        assert!(first.try_borrow().is_some());
+        assert!(first.try_borrow_mut().is_none());
+        column.release();
+        assert!(column.try_borrow_mut());
+        assert!(second.try_borrow().is_none());
+        column.release_mut();
+        assert_eq!(*first.borrow(), 3);
+        assert!(rows.iter().all(BorrowFlag::is_free));
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    any::Any,
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    archetype::Archetype, column::ComponentFetch, Access, Component, Entity, QueryFilter, World, WorldQuery,
+};
+
+/// How many ticks pass between two [World::check_change_ticks()] passes.
+pub(crate) const CHECK_TICK_THRESHOLD: u32 = 518_400_000;
+/// Age of the oldest tick kept. Older ticks are clamped to it, so a tick checked at least every [CHECK_TICK_THRESHOLD]
+/// ticks never gets old enough to wrap around and look new again.
+pub(crate) const MAX_CHANGE_AGE: u32 = u32::MAX - (2 * CHECK_TICK_THRESHOLD - 1);
+
+/// Moves `tick` forward if it is older than [MAX_CHANGE_AGE], it stays older than every tick it was compared against.
+pub(crate) fn clamp_tick(tick: &mut u32, change_tick: u32) {
+    if change_tick.wrapping_sub(*tick) > MAX_CHANGE_AGE {
+        *tick = change_tick.wrapping_sub(MAX_CHANGE_AGE);
+    }
+}
+
+/// Change ticks of a single component: when it was added to its entity and when it was last inserted or mutably borrowed.
+pub struct ComponentTicks {
+    added: u32,
+    changed: AtomicU32,
+}
+
+impl ComponentTicks {
+    pub(crate) fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: AtomicU32::new(tick),
+        }
+    }
+
+    pub(crate) fn set_changed(&self, tick: u32) {
+        self.changed.store(tick, Ordering::Relaxed);
+    }
+
+    pub(crate) fn check_change_ticks(&mut self, change_tick: u32) {
+        clamp_tick(&mut self.added, change_tick);
+        clamp_tick(self.changed.get_mut(), change_tick);
+    }
+
+    fn is_added(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.added, last_run, this_run)
+    }
+
+    fn is_changed(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.changed.load(Ordering::Relaxed), last_run, this_run)
+    }
+}
+
+/// Returns true if `tick` happened after `last_run`. Ticks are compared relative to `this_run` so they can wrap around.
+fn is_newer(tick: u32, last_run: u32, this_run: u32) -> bool {
+    this_run.wrapping_sub(tick) < this_run.wrapping_sub(last_run)
+}
+
+/// Ticks a query compares component ticks against, so every system only sees changes made since its own previous run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemTicks {
+    /// Changes after this tick are reported by [Added] and [Changed].
+    pub last_run: u32,
+    /// Tick stamped on components borrowed mutably.
+    pub this_run: u32,
+}
+
+/// Fetch of [Added] and [Changed]: the storage of the component and the ticks to compare against.
+pub struct TicksFetch<'w, T> {
+    component: ComponentFetch<'w, T>,
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<'w, T: Any> TicksFetch<'w, T> {
+    fn new(
+        state: &Option<usize>,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self> {
+        Some(Self {
+            component: ComponentFetch::new((*state)?, world, archetype)?,
+            last_run: ticks.last_run,
+            this_run: ticks.this_run,
+        })
+    }
+}
+
+/// Filter matching entities whose component `T` was added since the last run of the current system
+/// (or since the last [World::clear_trackers()] outside of systems).
+pub struct Added<T>(PhantomData<T>);
+
+impl<T: Component> WorldQuery for Added<T> {
+    type State = Option<usize>;
+    type Fetch<'w> = TicksFetch<'w, T>;
+
+    fn init_state(world: &World) -> Self::State {
+        <&T>::init_state(world)
+    }
+
+    fn access(access: &mut Access) {
+        access.read_component::<T>();
+    }
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        TicksFetch::new(state, world, archetype, ticks)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+        fetch
+            .component
+            .ticks(row, entity)
+            .is_some_and(|ticks| ticks.is_added(fetch.last_run, fetch.this_run))
+    }
+}
+
+impl<T: Component> QueryFilter for Added<T> {}
+
+/// Filter matching entities whose component `T` was inserted or mutably borrowed since the last run of the current system
+/// (or since the last [World::clear_trackers()] outside of systems). Newly added components count as changed too.
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component> WorldQuery for Changed<T> {
+    type State = Option<usize>;
+    type Fetch<'w> = TicksFetch<'w, T>;
+
+    fn init_state(world: &World) -> Self::State {
+        <&T>::init_state(world)
+    }
+
+    fn access(access: &mut Access) {
+        access.read_component::<T>();
+    }
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        TicksFetch::new(state, world, archetype, ticks)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+        fetch
+            .component
+            .ticks(row, entity)
+            .is_some_and(|ticks| ticks.is_changed(fetch.last_run, fetch.this_run))
+    }
+}
+
+impl<T: Component> QueryFilter for Changed<T> {}
+
+#[cfg(test)]
+mod test {
+    use crate::change_detection::{clamp_tick, is_newer, MAX_CHANGE_AGE};
+
+    #[test]
+    fn ticks_compare_across_wrap_around() {
+        assert!(is_newer(5, 4, 6));
+        assert!(!is_newer(4, 4, 6));
+        assert!(!is_newer(3, 4, 6));
+        assert!(is_newer(1, u32::MAX - 1, 2));
+        assert!(!is_newer(u32::MAX - 2, u32::MAX - 1, 2));
+    }
+
+    #[test]
+    fn only_old_ticks_are_clamped() {
+        let mut tick = 10;
+        clamp_tick(&mut tick, 20);
+        assert_eq!(tick, 10);
+        clamp_tick(&mut tick, MAX_CHANGE_AGE + 20);
+        assert_eq!(tick, 20);
+        clamp_tick(&mut tick, 5); // the counter wrapped around
+        assert_eq!(tick, 5u32.wrapping_sub(MAX_CHANGE_AGE));
+    }
+}
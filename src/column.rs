@@ -0,0 +1,216 @@
+use std::{any::Any, cell::UnsafeCell};
+
+use crate::{
+    archetype::Archetype, cell::BorrowFlag, change_detection::ComponentTicks, sparse_set::SparseSet, ComponentCell,
+    Entity, World,
+};
+
+/// Type erased access to a [Column] so archetypes can move rows without knowing component types.
+pub(crate) trait AnyColumn: Any + Send + Sync {
+    /// Removes the row by moving the last one into its place, the removed value is dropped.
+    fn swap_remove(&mut self, row: usize);
+
+    /// Removes the row like [AnyColumn::swap_remove()] but pushes the value into `target` instead of dropping it.
+    /// `target` has to be a column of the same type.
+    fn move_row(&mut self, row: usize, target: &mut dyn AnyColumn);
+
+    fn reserve(&mut self, additional: usize);
+
+    /// Clamps ticks too old to compare, see [World::check_change_ticks()].
+    fn check_change_ticks(&mut self, change_tick: u32);
+
+    fn get(&self, row: usize) -> Option<ComponentCell<'_, dyn Any>>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Dense storage of one component type inside an archetype, row `n` belongs to the `n`th entity of the archetype.
+///
+/// Every row is borrowed on its own through [ComponentCell], [crate::Query::for_each()] borrows the whole column
+/// once per archetype instead, see [ColumnBorrow].
+pub struct Column<T> {
+    data: Vec<UnsafeCell<T>>, // same layout as `Vec<T>`, values are only reached through the borrow flags
+    ticks: Vec<ComponentTicks>,
+    borrows: Vec<BorrowFlag>, // one per row
+    column_borrow: BorrowFlag,
+}
+
+// SAFETY: values are only reached through borrows checked by the flags, like with a `RwLock`
+unsafe impl<T: Send + Sync> Sync for Column<T> {}
+
+impl<T: Any + Send + Sync> Column<T> {
+    pub(crate) fn new_boxed() -> Box<dyn AnyColumn> {
+        Box::new(Self {
+            data: vec![],
+            ticks: vec![],
+            borrows: vec![],
+            column_borrow: BorrowFlag::default(),
+        })
+    }
+
+    /// Adds the value as a component added at `tick`.
+    pub fn push(&mut self, value: T, tick: u32) {
+        self.data.push(UnsafeCell::new(value));
+        self.ticks.push(ComponentTicks::new(tick));
+        self.borrows.push(BorrowFlag::default());
+    }
+
+    /// Removes the row like [AnyColumn::swap_remove()] and returns its value.
+    pub fn take(&mut self, row: usize) -> T {
+        self.ticks.swap_remove(row);
+        self.borrows.swap_remove(row);
+        self.data.swap_remove(row).into_inner()
+    }
+
+    /// Overwrites the value, which counts as a change at `tick`.
+    pub fn replace(&mut self, row: usize, value: T, tick: u32) {
+        *self.data[row].get_mut() = value;
+        self.ticks[row].set_changed(tick);
+    }
+}
+
+impl<T> Column<T> {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn get(&self, row: usize) -> Option<ComponentCell<'_, T>> {
+        let value = self.data.get(row)?;
+        Some(ComponentCell::new(&self.borrows[row], Some(&self.column_borrow), value))
+    }
+
+    /// Borrows every row at once, returns None if one of them is borrowed in a conflicting way.
+    pub fn borrow_column(&self, exclusive: bool) -> Option<ColumnBorrow<'_>> {
+        ColumnBorrow::new(&self.column_borrow, &self.borrows, exclusive)
+    }
+
+    pub fn ticks(&self, row: usize) -> Option<&ComponentTicks> {
+        self.ticks.get(row)
+    }
+}
+
+impl<T: Any + Send + Sync> AnyColumn for Column<T> {
+    fn swap_remove(&mut self, row: usize) {
+        self.data.swap_remove(row);
+        self.ticks.swap_remove(row);
+        self.borrows.swap_remove(row);
+    }
+
+    fn move_row(&mut self, row: usize, target: &mut dyn AnyColumn) {
+        let target = target
+            .as_any_mut()
+            .downcast_mut::<Self>()
+            .expect("columns of one component always have the same type");
+        target.data.push(self.data.swap_remove(row));
+        target.ticks.push(self.ticks.swap_remove(row));
+        target.borrows.push(self.borrows.swap_remove(row));
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.ticks.reserve(additional);
+        self.borrows.reserve(additional);
+    }
+
+    fn check_change_ticks(&mut self, change_tick: u32) {
+        for ticks in &mut self.ticks {
+            ticks.check_change_ticks(change_tick);
+        }
+    }
+
+    fn get(&self, row: usize) -> Option<ComponentCell<'_, dyn Any>> {
+        let value: &UnsafeCell<dyn Any> = self.data.get(row)?;
+        Some(ComponentCell::new(&self.borrows[row], Some(&self.column_borrow), value))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Borrow of every row of a column at once, so [crate::Query::for_each()] can hand out the rows of an archetype
+/// without borrowing each of them. Rows borrowed on their own through [ComponentCell] keep working as long as
+/// they do not conflict.
+pub struct ColumnBorrow<'w> {
+    borrow: &'w BorrowFlag,
+    exclusive: bool,
+}
+
+impl<'w> ColumnBorrow<'w> {
+    fn new(borrow: &'w BorrowFlag, rows: &'w [BorrowFlag], exclusive: bool) -> Option<Self> {
+        let taken = match exclusive {
+            true => borrow.try_borrow_mut(),
+            false => borrow.try_borrow(),
+        };
+        if !taken {
+            return None;
+        }
+        let column_borrow = Self { borrow, exclusive };
+        // checked after taking the column borrow, rows do it the other way around, see BorrowFlag
+        let conflict = match exclusive {
+            true => rows.iter().any(|row| !row.is_free()),
+            false => rows.iter().any(BorrowFlag::is_exclusive),
+        };
+        (!conflict).then_some(column_borrow)
+    }
+}
+
+impl Drop for ColumnBorrow<'_> {
+    fn drop(&mut self) {
+        match self.exclusive {
+            true => self.borrow.release_mut(),
+            false => self.borrow.release(),
+        }
+    }
+}
+
+/// Storage of one component type inside a single archetype, resolved once before iterating its rows.
+pub enum ComponentFetch<'w, T> {
+    Table(&'w Column<T>),
+    Sparse(&'w SparseSet),
+}
+
+impl<'w, T: Any> ComponentFetch<'w, T> {
+    /// Returns None if entities of the archetype can not have the component.
+    pub fn new(component_id: usize, world: &'w World, archetype: &'w Archetype) -> Option<Self> {
+        if let Some(sparse_set) = world.sparse_sets.get(&component_id) {
+            return Some(Self::Sparse(sparse_set));
+        }
+        archetype.typed_column(component_id).map(Self::Table)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        match self {
+            Self::Table(_) => true,
+            Self::Sparse(sparse_set) => sparse_set.contains(entity),
+        }
+    }
+
+    pub fn get(&self, row: usize, entity: Entity) -> Option<ComponentCell<'w, T>> {
+        match self {
+            Self::Table(column) => column.get(row),
+            Self::Sparse(sparse_set) => sparse_set.typed_get(entity),
+        }
+    }
+
+    /// Borrows the whole column, for sparse sets also the components of entities in other archetypes.
+    pub fn borrow_column(&self, exclusive: bool) -> Option<ColumnBorrow<'w>> {
+        match self {
+            Self::Table(column) => column.borrow_column(exclusive),
+            Self::Sparse(sparse_set) => sparse_set.typed_dense::<T>()?.borrow_column(exclusive),
+        }
+    }
+
+    pub fn ticks(&self, row: usize, entity: Entity) -> Option<&'w ComponentTicks> {
+        match self {
+            Self::Table(column) => column.ticks(row),
+            Self::Sparse(sparse_set) => sparse_set.typed_ticks::<T>(entity),
+        }
+    }
+}
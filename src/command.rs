@@ -0,0 +1,132 @@
+use std::sync::PoisonError;
+
+use crate::{Bundle, EcsError, Entity, World};
+
+/// Structural change waiting in the queue of a [World] until [World::apply_commands()].
+pub(crate) type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/**
+Queue of structural changes (spawning, despawning, inserting and removing components) which can be recorded while the world is borrowed,
+for example in the middle of iterating a query. Created with [World::commands()].
+
+Recorded commands are applied in order by [World::apply_commands()], which [crate::Systems] call at the end of every stage.
+Commands whose entity no longer exists when they are applied are skipped. Any other failure, like inserting a bundle
+which contains the same component twice, is a bug in the command: it panics in debug builds and is skipped in release builds.
+
+Example:
+```
+use wgtr_ecs::*;
+#[derive(Component)]
+struct Health(u32);
+
+let mut world = World::new();
+world.register_component::<Health>();
+world.create_entity().with_component(Health(0)).unwrap();
+
+let mut commands = world.commands();
+for (entity, health) in world.query::<(Entity, &Health)>().run() {
+    if health.0 == 0 {
+        commands.despawn(entity);
+    }
+}
+let spawned = commands.spawn().insert(Health(100)).id();
+
+world.apply_commands();
+assert_eq!(world.query::<&Health>().run().len(), 1);
+assert!(world.is_alive(spawned));
+```
+ */
+pub struct Commands<'w, 's> {
+    world: &'w World,
+    queue: Option<&'s mut Vec<Command>>, // queue of a system, moved to the world after the system runs; None for the world queue
+}
+
+impl<'w, 's> Commands<'w, 's> {
+    pub(crate) fn new(world: &'w World, queue: Option<&'s mut Vec<Command>>) -> Self {
+        Self { world, queue }
+    }
+
+    /// Queues a custom command.
+    pub fn add(&mut self, command: impl FnOnce(&mut World) + Send + 'static) {
+        match &mut self.queue {
+            Some(queue) => queue.push(Box::new(command)),
+            None => self
+                .world
+                .command_queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(Box::new(command)),
+        }
+    }
+
+    /// Reserves a handle for a new entity right away, the entity itself is created when the commands are applied.
+    pub fn spawn(&mut self) -> EntityCommands<'_, 'w, 's> {
+        let entity = self.world.entities.reserve();
+        EntityCommands {
+            commands: self,
+            entity,
+        }
+    }
+
+    /// Returns a builder queueing commands for an existing entity.
+    pub fn entity(&mut self, entity: Entity) -> EntityCommands<'_, 'w, 's> {
+        EntityCommands {
+            commands: self,
+            entity,
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            skip_missing_entity(world.remove_entity(entity));
+        });
+    }
+
+    /// Adds the component or bundle to the entity, replacing the components it already has, same as [World::add_bundle()].
+    pub fn insert<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        self.add(move |world| {
+            skip_missing_entity(world.add_bundle(bundle, entity));
+        });
+    }
+
+    /// Removes the component or every component of the bundle, same as [World::remove_bundle()].
+    pub fn remove<B: Bundle>(&mut self, entity: Entity) {
+        self.add(move |world| {
+            skip_missing_entity(world.remove_bundle::<B>(entity));
+        });
+    }
+}
+
+/// Entities can be removed before their commands are applied, see [Commands] for the other failures.
+fn skip_missing_entity(result: Result<(), EcsError>) {
+    if let Err(error) = result {
+        debug_assert!(matches!(error, EcsError::NoSuchEntity(_)), "Command failed: {error}");
+    }
+}
+
+/// Builder returned by [Commands::spawn()] and [Commands::entity()] for queueing commands of a single entity.
+pub struct EntityCommands<'c, 'w, 's> {
+    commands: &'c mut Commands<'w, 's>,
+    entity: Entity,
+}
+
+impl EntityCommands<'_, '_, '_> {
+    /// Handle of the entity, valid even before the commands are applied.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn insert<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        self.commands.insert(self.entity, bundle);
+        self
+    }
+
+    pub fn remove<B: Bundle>(&mut self) -> &mut Self {
+        self.commands.remove::<B>(self.entity);
+        self
+    }
+
+    pub fn despawn(&mut self) {
+        self.commands.despawn(self.entity);
+    }
+}
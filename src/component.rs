@@ -0,0 +1,119 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+};
+
+use crate::{
+    column::{AnyColumn, Column},
+    Entity, World,
+};
+
+/// Data which can be attached to entities, usually implemented with `#[derive(Component)]`.
+///
+/// Only types implementing it can be inserted, so a stray `10` can not end up stored as an `i32` component.
+/// The derive configures the associated items with the `component` attribute:
+///
+/// ```
+/// use wgtr_ecs::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+///
+/// #[derive(Component)]
+/// #[component(storage = "sparse_set", on_insert = mark_burning, name = "burning")]
+/// struct Burning;
+///
+/// fn mark_burning(world: &mut World, entity: Entity) {
+///     world.add_component(Health(1), entity).unwrap();
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.create_entity().with_component(Burning).unwrap().id();
+/// assert_eq!(world.query::<&Health>().get(entity).unwrap().0, 1);
+/// assert_eq!(Burning::serialization_name(), "burning");
+/// ```
+///
+/// Plain values are rejected at compile time:
+///
+/// ```compile_fail
+/// use wgtr_ecs::*;
+/// let mut world = World::new();
+/// world.create_entity().with_component(10).unwrap();
+/// ```
+pub trait Component: Send + Sync + 'static {
+    /// Storage used when the component is registered by inserting it or with [World::register_component()].
+    const STORAGE: StorageType = StorageType::Table;
+
+    /// Name of the component for saving and loading, also for `#[derive(Component)]` without `name`.
+    ///
+    /// Defaults to [type_name] with the module path and generic arguments, like `game::Tagged<u32>`, so two component
+    /// types never share it. Set a name which does not change when the type is moved with `#[component(name = "...")]`.
+    fn serialization_name() -> &'static str {
+        type_name::<Self>()
+    }
+
+    /// Called after the component is added to the entity, also when it replaces a previous value.
+    fn on_insert(_world: &mut World, _entity: Entity) {}
+
+    /// Called before the component is removed from the entity, also when the whole entity is removed.
+    ///
+    /// The component is still there while the hook runs, so removing it or the entity right away would call the hook again,
+    /// queue that with [World::commands()] instead.
+    fn on_remove(_world: &mut World, _entity: Entity) {}
+}
+
+/// How components of one type are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageType {
+    /// Components live in dense archetype columns. Fastest to iterate but adding or removing
+    /// the component moves the whole entity to another archetype.
+    #[default]
+    Table,
+    /// Components live in a sparse set indexed by entity. Cheap to add and remove, meant for
+    /// marker-like components toggled often.
+    SparseSet,
+}
+
+/// Everything the world needs to know about a registered component type.
+pub(crate) struct ComponentInfo {
+    pub type_name: &'static str,
+    pub storage: StorageType,
+    pub new_column: fn() -> Box<dyn AnyColumn>,
+    // hooks called through the id where the type is not known, like in World::add_bundle and World::remove_entity
+    pub on_insert: fn(&mut World, Entity),
+    pub on_remove: fn(&mut World, Entity),
+}
+
+/// Registry handing out ids of component types. Ids are also the component bits used in [crate::World].
+#[derive(Default)]
+pub(crate) struct Components {
+    ids: HashMap<TypeId, usize>,
+    infos: Vec<ComponentInfo>,
+}
+
+impl Components {
+    pub fn register<T: Component>(&mut self, storage: StorageType) -> usize {
+        let id = self.infos.len();
+        self.infos.push(ComponentInfo {
+            type_name: type_name::<T>(),
+            storage,
+            new_column: Column::<T>::new_boxed,
+            on_insert: T::on_insert,
+            on_remove: T::on_remove,
+        });
+        self.ids.insert(TypeId::of::<T>(), id);
+        id
+    }
+
+    pub fn id(&self, type_id: TypeId) -> Option<usize> {
+        self.ids.get(&type_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.infos.len()
+    }
+
+    pub fn info(&self, id: usize) -> &ComponentInfo {
+        &self.infos[id]
+    }
+}
@@ -0,0 +1,259 @@
+use std::any::{Any, TypeId};
+
+use crate::{
+    archetype::EntityLocation, bit_set::BitSet, Component, ComponentCell, EcsError, Entity, QueryEntity, StorageType,
+    World,
+};
+
+type QueryResult<'a> = (Vec<Entity>, Vec<Vec<ComponentCell<'a, dyn Any>>>);
+
+/**
+Struct made for querying entities with specyfic components chosen at runtime. It is similar to builder pattern things but build function is either [DynamicQuery::run()] or [DynamicQuery::run_entity()].
+When the components are known at compile time prefer [crate::Query] which does not need any downcasting.
+ */
+pub struct DynamicQuery<'a> {
+    map: BitSet, // queried table components, sparse set components are checked per entity
+    matches_nothing: bool, // a queried component was never registered, so no entity can have it
+
+    world: &'a World,
+    type_ids: Vec<TypeId>,
+    component_ids: Vec<usize>,
+    sparse_ids: Vec<usize>,
+    excluded_ids: Vec<usize>,
+}
+
+impl<'a> DynamicQuery<'a> {
+    pub(crate) fn new(world: &'a World) -> Self {
+        Self {
+            map: BitSet::new(),
+            matches_nothing: false,
+            world,
+            type_ids: vec![],
+            component_ids: vec![],
+            sparse_ids: vec![],
+            excluded_ids: vec![],
+        }
+    }
+
+    /// Only matches entities which have the component. A component which was never registered nor inserted
+    /// matches nothing, like in [crate::Query].
+    pub fn with_component<T: Component>(&mut self) -> Result<&mut Self, EcsError> {
+        let type_id = TypeId::of::<T>();
+        self.type_ids.push(type_id);
+        let Some(component_id) = self.world.components.id(type_id) else {
+            self.matches_nothing = true;
+            return Ok(self);
+        };
+        match self.world.components.info(component_id).storage {
+            StorageType::Table => self.map.insert(component_id),
+            StorageType::SparseSet => self.sparse_ids.push(component_id),
+        }
+        self.component_ids.push(component_id);
+        Ok(self)
+    }
+
+    /// Skips entities which have the component. Unlike [DynamicQuery::with_component()] nothing is returned for it by [DynamicQuery::run()].
+    /// A component which was never registered nor inserted skips nothing.
+    pub fn without_component<T: Component>(&mut self) -> Result<&mut Self, EcsError> {
+        if let Some(component_id) = self.world.components.id(TypeId::of::<T>()) {
+            self.excluded_ids.push(component_id);
+        }
+        Ok(self)
+    }
+
+    /// Returns matching entities and, for every queried component in order of [DynamicQuery::with_component()] calls,
+    /// the components of those entities.
+    pub fn run(&self) -> QueryResult<'a> {
+        let mut entities = vec![];
+        let mut result = vec![vec![]; self.type_ids.len()];
+
+        for location in self.matches() {
+            let archetype = self.world.archetypes.get(location.archetype);
+            let entity = archetype.entities()[location.row];
+            entities.push(entity);
+            for (component_id, components_to_keep) in self.component_ids.iter().zip(&mut result) {
+                let component = match self.world.sparse_sets.get(component_id) {
+                    Some(sparse_set) => sparse_set.get(entity),
+                    None => archetype
+                        .column(*component_id)
+                        .and_then(|column| column.get(location.row)),
+                };
+                components_to_keep.push(component.expect("matching entity has every queried component"));
+            }
+        }
+
+        (entities, result)
+    }
+
+    pub fn run_entity(&self) -> Vec<QueryEntity<'a>> {
+        self.matches()
+            .map(|location| {
+                let entity = self.world.archetypes.get(location.archetype).entities()[location.row];
+                QueryEntity::new(entity, location, self.world)
+            })
+            .collect()
+    }
+
+    /// Locations of all entities which have every queried component and no excluded one, archetype after archetype.
+    fn matches(&self) -> impl Iterator<Item = EntityLocation> + '_ {
+        self.world
+            .archetypes
+            .iter()
+            .enumerate()
+            .filter(|(_, archetype)| {
+                !self.matches_nothing
+                    && archetype.components().contains_all(&self.map)
+                    && !self
+                        .excluded_ids
+                        .iter()
+                        .any(|component_id| archetype.components().contains(*component_id))
+            })
+            .flat_map(|(archetype_id, archetype)| {
+                archetype
+                    .entities()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entity)| {
+                        self.sparse_ids
+                            .iter()
+                            .all(|component_id| self.world.sparse_sets[component_id].contains(**entity))
+                            && !self.excluded_ids.iter().any(|component_id| {
+                                self.world
+                                    .sparse_sets
+                                    .get(component_id)
+                                    .is_some_and(|sparse_set| sparse_set.contains(**entity))
+                            })
+                    })
+                    .map(move |(row, _)| EntityLocation {
+                        archetype: archetype_id,
+                        row,
+                    })
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use crate::{{World, QueryEntity}, make_query, AtomicRef, AtomicRefMut, Component, EcsError};
+
+    #[derive(Component)]
+    struct Health(u32);
+    #[allow(dead_code)]
+    #[derive(Component)]
+    struct Speed(f32);
+
+    #[test]
+    fn query_mask_updating_with_component() -> Result<(), EcsError> {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
+        let mut query = world.dynamic_query();
+        query.with_component::<Health>()?.with_component::<Speed>()?;
+
+        assert!(query.map.contains(0));
+        assert!(query.map.contains(1));
+        assert_eq!(TypeId::of::<Health>(), query.type_ids[0]);
+        assert_eq!(TypeId::of::<Speed>(), query.type_ids[1]);
+
+        Ok(())
+    }
+    #[test]
+    fn macro_query_mask_updating_with_component() -> Result<(), EcsError>{
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
+        let mut query = world.dynamic_query();
+        make_query!(query, Health, Speed);
+
+        assert!(query.map.contains(0));
+        assert!(query.map.contains(1));
+        assert_eq!(TypeId::of::<Health>(), query.type_ids[0]);
+        assert_eq!(TypeId::of::<Speed>(), query.type_ids[1]);
+
+        Ok(())
+    }
+    #[test]
+    fn run_query() -> Result<(), EcsError> {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
+
+        world.create_entity().with_component(Health(5))?;
+        let entity = world
+            .create_entity()
+            .with_component(Health(420))?
+            .with_component(Speed(11.1))?
+            .id();
+        world.create_entity().with_component(Speed(0.0))?;
+
+        let mut query = world.dynamic_query();
+        query.with_component::<Health>()?.with_component::<Speed>()?;
+        let query_result = query.run();
+
+        let healths = &query_result.1[0];
+        let speeds = &query_result.1[1];
+        let entities = &query_result.0;
+        assert_eq!(healths.len(), 1);
+        assert_eq!(speeds.len(), 1);
+        assert_eq!(healths.len(), entities.len());
+        assert_eq!(entities[0], entity);
+
+        let first_health = healths[0].borrow();
+        let extracted_health = first_health.downcast_ref::<Health>().unwrap();
+        assert_eq!(extracted_health.0, 420);
+
+        Ok(())
+    }
+    #[test]
+    fn query_for_entity_ref() -> Result<(), EcsError> {
+        let mut world = World::new();
+
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
+        let first = world.create_entity().with_component(Health(100))?.id();
+        world.create_entity().with_component(Speed(10.0))?;
+
+        let mut query = world.dynamic_query();
+        let entities: Vec<QueryEntity> = query.with_component::<Health>()?.run_entity();
+
+        assert_eq!(entities.len(), 1);
+
+        for entity in entities {
+            assert_eq!(entity.id, first);
+            let health: AtomicRef<Health> = entity.get_component::<Health>()?;
+            assert_eq!(health.0, 100);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn query_for_entity_mut() -> Result<(), EcsError> {
+        let mut world = World::new();
+
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
+        let first = world.create_entity().with_component(Health(100))?.id();
+        world.create_entity().with_component(Speed(10.0))?;
+
+        let mut query = world.dynamic_query();
+        let entities: Vec<QueryEntity> = query.with_component::<Health>()?.run_entity();
+
+        assert_eq!(entities.len(), 1);
+
+        for entity in entities {
+            assert_eq!(entity.id, first);
+            let mut health: AtomicRefMut<Health> = entity.get_component_mut::<Health>()?;
+            assert_eq!(health.0, 100);
+            health.0 += 1;
+        }
+
+        let entities: Vec<QueryEntity> = query.with_component::<Health>()?.run_entity();
+        for entity in entities {
+            let health: AtomicRef<Health> = entity.get_component::<Health>()?;
+            assert_eq!(health.0, 101);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{archetype::EntityLocation, Component, EcsError, World};
+
+/// Handle to an entity living in a [crate::World].
+///
+/// Besides the slot index it carries the generation of that slot, so a handle kept after
+/// [crate::World::remove_entity()] never points at an entity created later in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    /// Slot of the entity inside the world.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// How many times the slot was reused before this entity was created.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Bookkeeping of entity slots. Removed slots go to the free list and are handed out again
+/// with a bumped generation.
+///
+/// Handles can also be reserved through a shared reference, they become real entities on [Entities::flush()].
+#[derive(Default)]
+pub(crate) struct Entities {
+    slots: Vec<Slot>,
+    free_spots: Vec<usize>, // slots of removed entities waiting to be reused
+    reserved: AtomicUsize,  // handles given out by `reserve` and not flushed yet
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    generation: u32, // how many times the slot was freed
+    location: Option<EntityLocation>, // None while the slot is free
+}
+
+impl Entities {
+    /// Returns a handle for a new entity living at `location`.
+    /// Reserved handles have to be flushed first, otherwise they could be handed out twice.
+    pub fn alloc(&mut self, location: EntityLocation) -> Entity {
+        debug_assert_eq!(self.reserved.load(Ordering::Relaxed), 0, "reserved entities were not flushed");
+        if let Some(index) = self.free_spots.pop() {
+            let slot = &mut self.slots[index];
+            slot.location = Some(location);
+            return Entity::new(index, slot.generation);
+        }
+        self.slots.push(Slot {
+            generation: 0,
+            location: Some(location),
+        });
+        Entity::new(self.slots.len() - 1, 0)
+    }
+
+    /// Frees the slot of the entity. Returns false if the entity was not alive.
+    pub fn free(&mut self, entity: Entity) -> bool {
+        debug_assert_eq!(self.reserved.load(Ordering::Relaxed), 0, "reserved entities were not flushed");
+        if !self.contains(entity) {
+            return false;
+        }
+        let slot = &mut self.slots[entity.index()];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.location = None;
+        self.free_spots.push(entity.index());
+        true
+    }
+
+    /// Returns the handle the next flushed entity will get. Free slots are taken from the end
+    /// of the free list, same as [Entities::alloc()] does, after them come slots past the end.
+    pub fn reserve(&self) -> Entity {
+        let reserved = self.reserved.fetch_add(1, Ordering::Relaxed);
+        match self.free_spots.len().checked_sub(reserved + 1) {
+            Some(free_spot) => {
+                let index = self.free_spots[free_spot];
+                Entity::new(index, self.slots[index].generation)
+            }
+            None => Entity::new(self.slots.len() + reserved - self.free_spots.len(), 0),
+        }
+    }
+
+    /// Turns reserved handles into allocated slots in the order they were reserved.
+    /// Their locations are not known yet and have to be set with [Entities::set_location()].
+    pub fn flush(&mut self) -> Vec<Entity> {
+        let reserved = std::mem::take(self.reserved.get_mut());
+        let mut flushed = Vec::with_capacity(reserved);
+        for _ in 0..reserved {
+            let index = match self.free_spots.pop() {
+                Some(index) => index,
+                None => {
+                    self.slots.push(Slot {
+                        generation: 0,
+                        location: None,
+                    });
+                    self.slots.len() - 1
+                }
+            };
+            flushed.push(Entity::new(index, self.slots[index].generation));
+        }
+        flushed
+    }
+
+    /// Makes room for `additional` more entities, free slots count towards them.
+    pub fn reserve_slots(&mut self, additional: usize) {
+        self.slots.reserve(additional.saturating_sub(self.free_spots.len()));
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    /// Location of the entity or None if it is not alive.
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        let slot = self.slots.get(entity.index())?;
+        if slot.generation != entity.generation() {
+            return None;
+        }
+        slot.location
+    }
+
+    pub fn set_location(&mut self, entity: Entity, location: EntityLocation) {
+        self.slots[entity.index()].location = Some(location);
+    }
+}
+
+/// Builder returned by [crate::World::create_entity()] for attaching components to a freshly created entity.
+///
+/// Example:
+/// ```
+/// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+///
+/// let mut world = World::new();
+/// let entity = world.create_entity().with_component(Health(10)).unwrap().id();
+/// assert!(world.is_alive(entity));
+/// ```
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityBuilder<'a> {
+    pub(crate) fn new(world: &'a mut World, entity: Entity) -> Self {
+        Self { world, entity }
+    }
+
+    /// Handle of the entity being built.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn with_component(&mut self, data: impl Component) -> Result<&mut Self, EcsError> {
+        self.world.add_component(data, self.entity)?;
+        Ok(self)
+    }
+}
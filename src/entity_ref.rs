@@ -0,0 +1,146 @@
+use std::any::TypeId;
+
+use crate::{
+    archetype::EntityLocation, column::ComponentFetch, AtomicRef, AtomicRefMut, BorrowState, Bundle, Component,
+    ComponentCell, EcsError, Entity, World,
+};
+
+/// Shared access to the components of a single entity, returned by [World::entity()].
+///
+/// Components are borrowed at runtime like in queries, so [EntityRef::get_mut()] works through a shared reference
+/// and fails if the component is already borrowed.
+#[derive(Clone, Copy)]
+pub struct EntityRef<'w> {
+    world: &'w World,
+    entity: Entity,
+    location: EntityLocation,
+}
+
+impl<'w> EntityRef<'w> {
+    pub(crate) fn new(world: &'w World, entity: Entity, location: EntityLocation) -> Self {
+        Self {
+            world,
+            entity,
+            location,
+        }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Returns true if the entity has the component.
+    pub fn contains<T: Component>(&self) -> bool {
+        self.fetch::<T>().is_ok()
+    }
+
+    /// Returns an error if the entity does not have the component or it is borrowed mutably.
+    pub fn get<T: Component>(&self) -> Result<AtomicRef<'w, T>, EcsError> {
+        let (cell, _) = self.fetch::<T>()?;
+        cell.try_borrow()
+            .ok_or(EcsError::borrowed::<T>(BorrowState::BorrowedMutably))
+    }
+
+    /// Borrows the component mutably and marks it as changed, see [crate::Changed].
+    /// Returns an error if the entity does not have the component or it is borrowed.
+    pub fn get_mut<T: Component>(&self) -> Result<AtomicRefMut<'w, T>, EcsError> {
+        let (cell, fetch) = self.fetch::<T>()?;
+        let component = cell
+            .try_borrow_mut()
+            .ok_or(EcsError::borrowed::<T>(BorrowState::Borrowed))?;
+        if let Some(ticks) = fetch.ticks(self.location.row, self.entity) {
+            ticks.set_changed(self.world.change_tick);
+        }
+        Ok(component)
+    }
+
+    fn fetch<T: Component>(&self) -> Result<(ComponentCell<'w, T>, ComponentFetch<'w, T>), EcsError> {
+        let component_id = self
+            .world
+            .components
+            .id(TypeId::of::<T>())
+            .ok_or(EcsError::missing::<T>(self.entity))?;
+        let archetype = self.world.archetypes.get(self.location.archetype);
+        let fetch =
+            ComponentFetch::new(component_id, self.world, archetype).ok_or(EcsError::missing::<T>(self.entity))?;
+        let cell = fetch
+            .get(self.location.row, self.entity)
+            .ok_or(EcsError::missing::<T>(self.entity))?;
+        Ok((cell, fetch))
+    }
+}
+
+/// Exclusive access to a single entity, returned by [World::entity_mut()]. Besides reading components
+/// it can change the components of the entity and despawn it.
+///
+/// Example:
+/// ```
+/// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Poisoned;
+///
+/// let mut world = World::new();
+/// let entity = world.spawn((Health(10), Poisoned));
+///
+/// let mut entity_mut = world.entity_mut(entity).unwrap();
+/// entity_mut.get_mut::<Health>().unwrap().0 -= 1;
+/// entity_mut.remove::<Poisoned>().unwrap();
+/// assert!(!entity_mut.contains::<Poisoned>());
+///
+/// assert_eq!(world.get::<Health>(entity).unwrap().0, 9);
+/// world.entity_mut(entity).unwrap().despawn().unwrap();
+/// assert!(!world.is_alive(entity));
+/// ```
+pub struct EntityMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> EntityMut<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity) -> Self {
+        Self { world, entity }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Returns true if the entity has the component.
+    pub fn contains<T: Component>(&self) -> bool {
+        self.as_ref().is_ok_and(|entity| entity.contains::<T>())
+    }
+
+    /// Returns an error if the entity does not have the component or it is borrowed mutably.
+    pub fn get<T: Component>(&self) -> Result<AtomicRef<'_, T>, EcsError> {
+        self.as_ref()?.get()
+    }
+
+    /// Borrows the component mutably and marks it as changed, see [crate::Changed].
+    pub fn get_mut<T: Component>(&mut self) -> Result<AtomicRefMut<'_, T>, EcsError> {
+        self.as_ref()?.get_mut()
+    }
+
+    /// Adds the component or bundle, same as [World::add_bundle()].
+    pub fn insert<B: Bundle>(&mut self, bundle: B) -> Result<&mut Self, EcsError> {
+        self.world.add_bundle(bundle, self.entity)?;
+        Ok(self)
+    }
+
+    /// Removes the component or every component of the bundle, same as [World::remove_bundle()].
+    pub fn remove<B: Bundle>(&mut self) -> Result<&mut Self, EcsError> {
+        self.world.remove_bundle::<B>(self.entity)?;
+        Ok(self)
+    }
+
+    /// Removes the entity with all of its components, same as [World::remove_entity()].
+    pub fn despawn(self) -> Result<(), EcsError> {
+        self.world.remove_entity(self.entity)
+    }
+
+    /// Returns an error if one of the hooks removed the entity.
+    fn as_ref(&self) -> Result<EntityRef<'_>, EcsError> {
+        self.world.entity(self.entity)
+    }
+}
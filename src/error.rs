@@ -0,0 +1,123 @@
+use std::{
+    any::type_name,
+    error::Error,
+    fmt::{self, Display},
+};
+
+use crate::{Entity, StorageType};
+
+/// Error returned by fallible operations on the [crate::World] and its queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EcsError {
+    /// The entity was never created or was already removed.
+    NoSuchEntity(Entity),
+    /// The entity is alive but does not have the component.
+    MissingComponent { entity: Entity, type_name: &'static str },
+    /// The entity is alive but does not have the components of the query or is filtered out by it.
+    QueryMismatch(Entity),
+    /// The bundle contains the component more than once, returned by [crate::World::add_bundle()].
+    DuplicateComponent { type_name: &'static str },
+    /// The resource was never added or was already removed, returned by [crate::World::resource_scope()].
+    MissingResource { type_name: &'static str },
+    /// The component is already registered with another storage, returned by
+    /// [crate::World::register_component_with_storage()]. `storage` is the one it keeps.
+    StorageConflict { type_name: &'static str, storage: StorageType },
+    /// The component or resource is borrowed in a way which conflicts with the requested borrow.
+    AlreadyBorrowed { type_name: &'static str, state: BorrowState },
+}
+
+/// Borrow which is still alive, see [EcsError::AlreadyBorrowed].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorrowState {
+    Borrowed,
+    BorrowedMutably,
+    /// The resource is taken out of the world by [crate::World::resource_scope()].
+    Detached,
+}
+
+impl EcsError {
+    pub(crate) fn missing<T>(entity: Entity) -> Self {
+        Self::MissingComponent {
+            entity,
+            type_name: type_name::<T>(),
+        }
+    }
+
+    pub(crate) fn borrowed<T>(state: BorrowState) -> Self {
+        Self::AlreadyBorrowed {
+            type_name: type_name::<T>(),
+            state,
+        }
+    }
+}
+
+impl Display for EcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSuchEntity(entity) => write!(f, "Entity {entity:?} does not exist"),
+            Self::MissingComponent { entity, type_name } => {
+                write!(f, "Entity {entity:?} does not have component {type_name}")
+            }
+            Self::QueryMismatch(entity) => write!(f, "Entity {entity:?} does not match the query"),
+            Self::DuplicateComponent { type_name } => {
+                write!(f, "Bundle contains the same component more than once: {type_name}")
+            }
+            Self::MissingResource { type_name } => write!(f, "Resource {type_name} does not exist"),
+            Self::StorageConflict { type_name, storage } => {
+                write!(f, "Component {type_name} is already registered with {storage:?} storage")
+            }
+            Self::AlreadyBorrowed { type_name, state } => match state {
+                BorrowState::Borrowed => write!(f, "{type_name} is already borrowed"),
+                BorrowState::BorrowedMutably => write!(f, "{type_name} is already borrowed mutably"),
+                BorrowState::Detached => write!(f, "{type_name} is taken out of the world by World::resource_scope"),
+            },
+        }
+    }
+}
+
+impl Error for EcsError {}
+
+#[cfg(test)]
+mod test {
+    use crate::{BorrowState, Component, EcsError, World};
+
+    #[derive(Component)]
+    struct Health;
+    #[derive(Component)]
+    struct Speed;
+
+    #[test]
+    fn errors_name_the_failure() {
+        let mut world = World::new();
+        let entity = world.create_entity().id();
+
+        world.register_component::<Health>();
+        assert_eq!(world.query::<&Health>().get(entity).err(), Some(EcsError::QueryMismatch(entity)));
+        world.remove_entity(entity).unwrap();
+        let error = world.remove_entity(entity).unwrap_err();
+        assert_eq!(error, EcsError::NoSuchEntity(entity));
+        assert_eq!(error.to_string(), format!("Entity {entity:?} does not exist"));
+
+        let entity = world.create_entity().with_component(Health).unwrap().id();
+        let query_entity = world.dynamic_query().with_component::<Health>().unwrap().run_entity().remove(0);
+        assert_eq!(
+            query_entity.get_component::<Speed>().err(),
+            Some(EcsError::MissingComponent {
+                entity,
+                type_name: "wgtr_ecs::error::test::Speed"
+            })
+        );
+        let borrowed = query_entity.get_component_mut::<Health>().unwrap();
+        assert_eq!(
+            query_entity.get_component::<Health>().err(),
+            Some(EcsError::AlreadyBorrowed {
+                type_name: "wgtr_ecs::error::test::Health",
+                state: BorrowState::BorrowedMutably
+            })
+        );
+        drop(borrowed);
+
+        let error = EcsError::borrowed::<f32>(BorrowState::BorrowedMutably);
+        assert_eq!(error.to_string(), "f32 is already borrowed mutably");
+    }
+}
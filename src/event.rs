@@ -0,0 +1,229 @@
+use std::{any::Any, marker::PhantomData};
+
+use crate::{Res, ResMut, World};
+
+/**
+Double buffered queue of events of type `T`, stored as a resource added with [World::add_event()].
+
+Events are kept for two frames: [Events::update()], which [crate::Systems::update()] calls at the beginning of every frame,
+drops the events of the frame before the previous one. That way every system sees an event sent by any other system once,
+no matter if it runs before or after the sender, as long as it reads every frame.
+
+Function systems send events with [EventWriter] and read them with [EventReader], other code can use [EventCursor] directly.
+
+Example:
+```
+use wgtr_ecs::*;
+struct Collision(u32);
+
+let mut world = World::new();
+world.add_event::<Collision>();
+let mut cursor = EventCursor::<Collision>::new();
+
+world.event_writer::<Collision>().unwrap().send(Collision(7));
+let events = world.get_resource::<Events<Collision>>().unwrap();
+assert_eq!(cursor.read(&events).map(|collision| collision.0).collect::<Vec<_>>(), vec![7]);
+assert_eq!(cursor.read(&events).count(), 0);
+```
+ */
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    previous_start: usize, // count of events sent before the first one in `previous`
+    current_start: usize,  // count of events sent before the first one in `current`
+    event_count: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: vec![],
+            current: vec![],
+            previous_start: 0,
+            current_start: 0,
+            event_count: 0,
+        }
+    }
+}
+
+impl<T: Any> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.event_count += 1;
+    }
+
+    /// Swaps the buffers, dropping events which were sent before the previous update.
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+        self.previous_start = self.current_start;
+        self.current_start = self.event_count;
+    }
+
+    /// Drops all events, readers will continue with events sent after this call.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+        self.previous_start = self.event_count;
+        self.current_start = self.event_count;
+    }
+
+    /// Number of events in both buffers.
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Updater registered by [World::add_event()].
+    pub(crate) fn update_in_world(world: &mut World) {
+        if let Some(mut events) = world.get_resource_mut::<Self>() {
+            events.update();
+        }
+    }
+}
+
+/// Sends events of type `T`, created with [World::event_writer()] or used as a function system parameter.
+pub struct EventWriter<'w, T> {
+    events: ResMut<'w, Events<T>>,
+}
+
+impl<'w, T: Any> EventWriter<'w, T> {
+    pub(crate) fn new(events: ResMut<'w, Events<T>>) -> Self {
+        Self { events }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.events.send(event);
+        }
+    }
+}
+
+/// Position of a reader in the stream of events of type `T`. Every reader keeps its own cursor, so it should be stored in
+/// the system that uses it and it returns each event only once.
+pub struct EventCursor<T> {
+    last_event_count: usize, // events sent before this count were already read
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self {
+            last_event_count: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Any> EventCursor<T> {
+    /// Creates a cursor which will also return events that were sent before it was created and are still buffered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns events not read with this cursor yet, oldest first.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let previous = self.last_event_count.saturating_sub(events.previous_start);
+        let current = self.last_event_count.saturating_sub(events.current_start);
+        self.last_event_count = events.event_count;
+        events
+            .previous
+            .get(previous..)
+            .unwrap_or_default()
+            .iter()
+            .chain(events.current.get(current..).unwrap_or_default())
+    }
+
+    /// Number of events [EventCursor::read()] would return.
+    pub fn len(&self, events: &Events<T>) -> usize {
+        let previous = events.previous.len().saturating_sub(self.last_event_count.saturating_sub(events.previous_start));
+        let current = events.current.len().saturating_sub(self.last_event_count.saturating_sub(events.current_start));
+        previous + current
+    }
+
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+}
+
+/// Function system parameter reading events of type `T`, its cursor is kept between runs of the system.
+pub struct EventReader<'w, 's, T> {
+    cursor: &'s mut EventCursor<T>,
+    events: Res<'w, Events<T>>,
+}
+
+impl<'w, 's, T: Any> EventReader<'w, 's, T> {
+    pub(crate) fn new(cursor: &'s mut EventCursor<T>, events: Res<'w, Events<T>>) -> Self {
+        Self { cursor, events }
+    }
+
+    /// Returns events not read by this system yet, oldest first.
+    pub fn read(&mut self) -> impl Iterator<Item = &T> {
+        self.cursor.read(&self.events)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cursor.len(&self.events)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cursor.is_empty(&self.events)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{EventCursor, Events};
+
+    fn read(reader: &mut EventCursor<u32>, events: &Events<u32>) -> Vec<u32> {
+        reader.read(events).copied().collect()
+    }
+
+    #[test]
+    fn events_live_for_two_updates() {
+        let mut events = Events::new();
+        let mut early = EventCursor::new();
+        let mut late = EventCursor::new();
+
+        events.send(1);
+        assert_eq!(read(&mut early, &events), vec![1]);
+        events.update();
+        events.send(2);
+        assert_eq!(early.len(&events), 1);
+        assert_eq!(read(&mut early, &events), vec![2]);
+        assert_eq!(read(&mut late, &events), vec![1, 2]);
+
+        events.update();
+        events.update();
+        assert!(events.is_empty());
+        events.send(3);
+        assert_eq!(read(&mut early, &events), vec![3]);
+        assert!(!late.is_empty(&events));
+    }
+
+    #[test]
+    fn reader_skips_dropped_events() {
+        let mut events = Events::new();
+        let mut reader = EventCursor::new();
+        events.send(1);
+        events.update();
+        events.send(2);
+        events.update();
+        events.send(3);
+        assert_eq!(read(&mut reader, &events), vec![2, 3]);
+
+        events.clear();
+        events.send(4);
+        assert_eq!(read(&mut reader, &events), vec![4]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{any::type_name, marker::PhantomData};
+
+use crate::{Access, System, SystemParam, SystemParamItem, SystemTicks, World};
+
+/// Conversion into a [System], implemented for every [System] and for functions whose arguments are [SystemParam]s.
+/// The `Marker` only tells the two kinds of implementations apart.
+pub trait IntoSystem<Marker> {
+    type System: System;
+
+    fn into_system(self) -> Self::System;
+}
+
+impl<S: System> IntoSystem<()> for S {
+    type System = S;
+
+    fn into_system(self) -> S {
+        self
+    }
+}
+
+#[doc(hidden)]
+pub struct IsFunctionSystem;
+
+impl<Marker, F: SystemParamFunction<Marker> + Send> IntoSystem<(IsFunctionSystem, Marker)> for F {
+    type System = FunctionSystem<Marker, F>;
+
+    fn into_system(self) -> Self::System {
+        FunctionSystem {
+            function: self,
+            state: None,
+            access: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Function which can be called with its parameters fetched from the world, see [SystemParam].
+pub trait SystemParamFunction<Marker> {
+    type Param: SystemParam;
+
+    fn run(&mut self, param: SystemParamItem<'_, '_, Self::Param>);
+}
+
+macro_rules! impl_system_param_function {
+    ($($param:ident),*) => {
+        impl<Func, $($param: SystemParam),*> SystemParamFunction<fn($($param,)*)> for Func
+        where
+            for<'a> &'a mut Func: FnMut($($param),*) + FnMut($(SystemParamItem<$param>),*),
+        {
+            type Param = ($($param,)*);
+
+            #[allow(non_snake_case, clippy::too_many_arguments)]
+            fn run(&mut self, param: SystemParamItem<'_, '_, Self::Param>) {
+                // calling through a generic function makes the compiler pick the FnMut implementation
+                // taking the fetched items instead of the parameter types themselves
+                fn call_inner<$($param),*>(mut f: impl FnMut($($param),*), $($param: $param),*) {
+                    f($($param),*)
+                }
+                let ($($param,)*) = param;
+                call_inner(self, $($param),*)
+            }
+        }
+    };
+}
+
+impl_system_param_function!();
+impl_system_param_function!(A);
+impl_system_param_function!(A, B);
+impl_system_param_function!(A, B, C);
+impl_system_param_function!(A, B, C, D);
+impl_system_param_function!(A, B, C, D, E);
+impl_system_param_function!(A, B, C, D, E, F);
+impl_system_param_function!(A, B, C, D, E, F, G);
+impl_system_param_function!(A, B, C, D, E, F, G, H);
+
+/// [System] running a function on every update, created by [crate::Systems::with_system()] from a function.
+pub struct FunctionSystem<Marker, F: SystemParamFunction<Marker>> {
+    function: F,
+    state: Option<<F::Param as SystemParam>::State>, // created on the first init, update or access
+    access: Option<Access>,
+    _marker: PhantomData<fn() -> Marker>,
+}
+
+impl<Marker, F: SystemParamFunction<Marker> + Send> System for FunctionSystem<Marker, F> {
+    fn name(&self) -> String {
+        type_name::<F>().to_string()
+    }
+
+    fn init(&mut self, world: &mut World) {
+        self.state.get_or_insert_with(|| F::Param::init_state(world));
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.init(world);
+        self.run_shared(world, world.ticks());
+        self.apply_deferred(world);
+    }
+
+    fn access(&mut self, world: &mut World) -> Option<Access> {
+        self.init(world);
+        let access = self.access.get_or_insert_with(|| {
+            let mut access = Access::new();
+            F::Param::access(&mut access);
+            access
+        });
+        Some(access.clone())
+    }
+
+    /// Panics if the system was not initialized.
+    fn run_shared(&mut self, world: &World, ticks: SystemTicks) {
+        let state = self.state.as_mut().expect("system is initialized before it runs");
+        let param = F::Param::get_param(state, world, ticks);
+        self.function.run(param);
+    }
+
+    fn apply_deferred(&mut self, world: &mut World) {
+        if let Some(state) = &mut self.state {
+            F::Param::apply(state, world);
+        }
+    }
+}
@@ -1,32 +1,106 @@
+extern crate self as wgtr_ecs; // lets the derive macros refer to `::wgtr_ecs` inside the crate too
+
+mod access;
+mod archetype;
+mod bit_set;
+mod bundle;
+mod cell;
+mod change_detection;
+mod column;
+mod command;
+mod component;
+mod dynamic_query;
+mod entity;
+mod entity_ref;
+mod error;
+mod event;
+mod function_system;
+mod macros;
 mod query;
 mod query_entity;
-mod macros;
+mod resource;
+mod schedule;
+mod sparse_set;
 mod system;
-
+mod system_param;
+mod task_pool;
+
+pub use crate::access::Access;
+pub use crate::bundle::{Bundle, BundleWriter};
+pub use crate::cell::*;
+pub use crate::change_detection::{Added, Changed, SystemTicks};
+pub use crate::command::*;
+pub use crate::component::{Component, StorageType};
+pub use crate::dynamic_query::*;
+pub use crate::entity::*;
+pub use crate::entity_ref::{EntityMut, EntityRef};
+pub use crate::error::*;
+pub use crate::event::*;
+pub use crate::function_system::*;
 pub use crate::query::*;
 pub use crate::query_entity::*;
+pub use crate::resource::*;
+pub use crate::schedule::*;
 pub use crate::system::*;
+pub use crate::system_param::*;
+pub use wgtr_ecs_derive::{Bundle, Component};
+
+use crate::{
+    archetype::{Archetypes, EntityLocation},
+    change_detection::{clamp_tick, CHECK_TICK_THRESHOLD},
+    column::{AnyColumn, Column},
+    command::Command,
+    component::Components,
+    sparse_set::SparseSet,
+};
 
 use std::{
-    any::{Any, TypeId},
-    cell::RefCell,
-    collections::HashMap,
-    rc::Rc,
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, HashSet},
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
 };
 
-type Component = Rc<RefCell<dyn Any + 'static>>;
-
 /// Main struct which contains all the entities, components and resources.
-#[derive(Default)]
+///
+/// Components and resources have to be `Send + Sync`, so the world can be moved to another thread and systems can share it
+/// between threads, see [Systems]. Resources which are not `Send` are added with [World::add_non_send_resource()].
+///
+/// Entities with the same set of components share an archetype, which stores every component type in its own dense column.
+/// Components registered with [StorageType::SparseSet] are kept outside of archetypes in per type sparse sets.
 pub struct World {
-    resources: HashMap<TypeId, Box<dyn Any>>,
-    components: HashMap<TypeId, Vec<Option<Component>>>,
-    bit_masks: HashMap<TypeId, u128>, // every component has its own mask
-    bit_maps: Vec<u128>, // every entity has its map which shows which components does it has
-    
-
-    creature_id: usize,     // id of entity that is being now created
-    free_spots: Vec<usize>, // free spots to create entity after removing one
+    resources: HashMap<TypeId, AtomicRefCell<Box<dyn Any + Send + Sync>>>,
+    non_send_resources: HashMap<TypeId, NonSendCell>,
+    detached_resources: HashSet<TypeId>, // taken out by World::resource_scope
+    components: Components,
+    archetypes: Archetypes,
+    sparse_sets: HashMap<usize, SparseSet>,
+    entities: Entities,
+    command_queue: Mutex<Vec<Command>>,  // recorded by Commands, applied by World::apply_commands
+    event_updaters: Vec<fn(&mut World)>,  // swap buffers of every Events resource added with World::add_event
+
+    change_tick: u32,      // tick stamped on inserted and mutably borrowed components
+    last_change_tick: u32, // changes after this tick are reported by Added and Changed filters
+    last_check_tick: u32,  // change tick of the last World::check_change_ticks pass
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            resources: HashMap::new(),
+            non_send_resources: HashMap::new(),
+            detached_resources: HashSet::new(),
+            components: Components::default(),
+            archetypes: Archetypes::default(),
+            sparse_sets: HashMap::new(),
+            entities: Entities::default(),
+            command_queue: Mutex::new(vec![]),
+            event_updaters: vec![],
+            change_tick: 1,
+            last_change_tick: 0,
+            last_check_tick: 1,
+        }
+    }
 }
 
 impl World {
@@ -34,122 +108,444 @@ impl World {
         Self::default()
     }
 
-    pub fn register_component<T: Any>(&mut self) {
-        let type_id = TypeId::of::<T>();
-        self.components.insert(type_id, vec![]);
-        self.bit_masks.insert(type_id, 1 << self.bit_masks.len());
-    }
-
-    pub fn create_entity(&mut self) -> &mut Self {
-        if self.free_spots.len() > 0 && self.free_spots[0] != 0 {
-            // if there are free spots
-            let free_index = self.free_spots.last().unwrap();
-            self.creature_id = *free_index;
-            self.free_spots.pop();
-            return self;
-        }
-        self.creature_id = 0;
-        self.components
-            .iter_mut()
-            .for_each(|(_key, components)| components.push(None));
-        self.bit_maps.push(0);
-        self
-    }
-
-    pub fn remove_entity(&mut self, index: usize) -> Result<(), &'static str> {
-        let map = self
-            .bit_maps
-            .get_mut(index)
-            .ok_or_else(|| "Tried to remove entity that does not exist")?;
-        *map = 0;
-        if index != 0 {
-            self.free_spots.push(index);
+    /// Creates a world with room for `entities` entities, see [World::reserve_entities()].
+    pub fn with_capacity(entities: usize) -> Self {
+        let mut world = Self::default();
+        world.reserve_entities(entities);
+        world
+    }
+
+    /// Makes room for `additional` more entities, so creating them does not reallocate the entity bookkeeping.
+    /// Component columns are reserved per archetype by [World::spawn_batch()].
+    pub fn reserve_entities(&mut self, additional: usize) {
+        self.flush_entities();
+        self.entities.reserve_slots(additional);
+    }
+
+    /// Registers a component with its [Component::STORAGE]. Components are registered this way the first time they are inserted,
+    /// so calling it is optional. Registering a component again does nothing, whatever storage it was registered with.
+    pub fn register_component<T: Component>(&mut self) {
+        self.init_component::<T>(T::STORAGE);
+    }
+
+    /// Registers a component with the given storage instead of its [Component::STORAGE].
+    /// Queries work the same way for both kinds of storage.
+    ///
+    /// Has to be called before the component is first inserted. Registering a component again keeps its storage and data,
+    /// returns [EcsError::StorageConflict] if it is registered with another storage.
+    pub fn register_component_with_storage<T: Component>(&mut self, storage: StorageType) -> Result<(), EcsError> {
+        let component_id = self.init_component::<T>(storage);
+        match self.components.info(component_id).storage {
+            registered if registered == storage => Ok(()),
+            registered => Err(EcsError::StorageConflict {
+                type_name: type_name::<T>(),
+                storage: registered,
+            }),
         }
+    }
+
+    pub fn create_entity(&mut self) -> EntityBuilder<'_> {
+        self.flush_entities();
+        let archetype = self.archetypes.get_mut(Archetypes::EMPTY);
+        let row = archetype.len();
+        let location = EntityLocation {
+            archetype: Archetypes::EMPTY,
+            row,
+        };
+        let entity = self.entities.alloc(location);
+        archetype.push_entity(entity);
+        EntityBuilder::new(self, entity)
+    }
+
+    /// Returns true if the entity was created and has not been removed since.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Gives shared access to the components of the entity, returns an error if the entity does not exist.
+    pub fn entity(&self, entity: Entity) -> Result<EntityRef<'_>, EcsError> {
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(EcsError::NoSuchEntity(entity))?;
+        Ok(EntityRef::new(self, entity, location))
+    }
+
+    /// Gives exclusive access to the entity, which can also add and remove its components or despawn it.
+    /// Returns an error if the entity does not exist.
+    pub fn entity_mut(&mut self, entity: Entity) -> Result<EntityMut<'_>, EcsError> {
+        self.flush_entities();
+        if !self.is_alive(entity) {
+            return Err(EcsError::NoSuchEntity(entity));
+        }
+        Ok(EntityMut::new(self, entity))
+    }
+
+    /// Shortcut for `world.entity(entity)?.get::<T>()`, see [EntityRef::get()].
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<AtomicRef<'_, T>, EcsError> {
+        self.entity(entity)?.get()
+    }
+
+    /// Removes the entity with all of its components, calling [Component::on_remove()] for each of them first.
+    pub fn remove_entity(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.flush_entities();
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(EcsError::NoSuchEntity(entity))?;
+        let component_ids: Vec<usize> = (0..self.components.len())
+            .filter(|component_id| self.contains_component(entity, location, *component_id))
+            .collect();
+        for component_id in component_ids {
+            (self.components.info(component_id).on_remove)(self, entity);
+        }
+        let Some(location) = self.entities.location(entity) else {
+            return Ok(()); // removed by one of the hooks
+        };
+        let swapped = self
+            .archetypes
+            .get_mut(location.archetype)
+            .remove_row(location.row);
+        if let Some(swapped) = swapped {
+            self.entities.set_location(swapped, location);
+        }
+        for sparse_set in self.sparse_sets.values_mut() {
+            sparse_set.remove(entity);
+        }
+        self.entities.free(entity);
         Ok(())
     }
 
-    pub fn with_component(&mut self, data: impl Any) -> Result<&mut Self, &'static str> {
-        let type_id = data.type_id();
+    /// Creates an entity with every component of the bundle, like `world.spawn((Health(100), Speed(10)))`.
+    /// The entity is placed straight into its final archetype and [Component::on_insert()] is called for every component.
+    ///
+    /// Panics if the bundle contains the same component twice, see [World::try_spawn()].
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        self.try_spawn(bundle).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Same as [World::spawn()] but returns [EcsError::DuplicateComponent] instead of panicking,
+    /// nothing is spawned in that case.
+    pub fn try_spawn<B: Bundle>(&mut self, bundle: B) -> Result<Entity, EcsError> {
+        let component_ids = self.register_bundle::<B>()?;
+        let target = self.bundle_archetype(Archetypes::EMPTY, &component_ids);
+        Ok(self.spawn_in(bundle, target, &component_ids))
+    }
+
+    /// Spawns an entity for every bundle and returns their handles in order, like many calls to [World::spawn()].
+    /// Components are registered and the archetype is found only once, and every column of the archetype
+    /// reserves room for all the bundles the iterator reports with its size hint.
+    ///
+    /// Panics if the bundle contains the same component twice, like [World::spawn()], see [World::try_spawn_batch()].
+    ///
+    /// Example:
+    /// ```
+    /// use wgtr_ecs::*;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Bullet;
+    ///
+    /// let mut world = World::new();
+    /// let bullets = world.spawn_batch((0..1000).map(|index| (Position(index as f32), Bullet)));
+    /// assert_eq!(bullets.len(), 1000);
+    /// assert_eq!(world.query::<&Position>().get(bullets[10]).unwrap().0, 10.0);
+    /// ```
+    pub fn spawn_batch<B: Bundle>(&mut self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        self.try_spawn_batch(bundles).unwrap_or_else(|error| panic!("{error}"))
+    }
 
-        if self.creature_id != 0 {
-            // if there are free spots
-            let components = self.components.get_mut(&type_id).unwrap();
-            let indexed_component = components.get_mut(self.creature_id).unwrap();
-            *indexed_component = Some(Rc::new(RefCell::new(data)));
-            let bitmask = self.bit_masks.get(&type_id).unwrap();
-            self.bit_maps[self.creature_id] |= *bitmask;
+    /// Same as [World::spawn_batch()] but returns [EcsError::DuplicateComponent] instead of panicking.
+    /// The bundle is checked before anything is reserved, nothing is spawned in that case.
+    pub fn try_spawn_batch<B: Bundle>(
+        &mut self,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Result<Vec<Entity>, EcsError> {
+        let component_ids = self.register_bundle::<B>()?;
+        self.flush_entities();
+        let bundles = bundles.into_iter();
+        let (additional, _) = bundles.size_hint();
+        let target = self.bundle_archetype(Archetypes::EMPTY, &component_ids);
+
+        self.entities.reserve_slots(additional);
+        self.archetypes.get_mut(target).reserve(additional);
+        for component_id in &component_ids {
+            if let Some(sparse_set) = self.sparse_sets.get_mut(component_id) {
+                sparse_set.reserve(additional);
+            }
+        }
 
-            return Ok(self);
+        let mut entities = Vec::with_capacity(additional);
+        for bundle in bundles {
+            entities.push(self.spawn_in(bundle, target, &component_ids));
         }
+        Ok(entities)
+    }
 
-        let map_index = self.bit_maps.len() - 1;
+    /// Adds the component to the entity or replaces the one it already has, registering the component if needed.
+    /// Works for handles reserved with [Commands::spawn()] before the commands are applied.
+    ///
+    /// [Component::on_insert()] is called after the component is added.
+    pub fn add_component<T: Component>(&mut self, data: T, entity: Entity) -> Result<(), EcsError> {
+        self.add_bundle(data, entity)
+    }
 
-        if let Some(components) = self.components.get_mut(&type_id) {
-            let last_component = components
-                .last_mut()
-                .ok_or_else(|| "Could not find the last component")
-                .unwrap();
-            *last_component = Some(Rc::new(RefCell::new(data)));
-            let bitmask = self.bit_masks.get(&type_id).unwrap();
-            self.bit_maps[map_index] |= *bitmask;
-        } else {
-            return Err(
-                "Tried to use with_comopnent with a component that hasnt been registered",
-            );
+    /// Removes the component from the entity, does nothing if the entity does not have it.
+    ///
+    /// [Component::on_remove()] is called before the component is removed.
+    pub fn remove_component<T: Component>(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.remove_bundle::<T>(entity)
+    }
+
+    /// Removes the component from the entity and returns it, None if the entity does not exist or does not have it.
+    ///
+    /// [Component::on_remove()] is called before the component is taken.
+    pub fn take_component<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.flush_entities();
+        let location = self.entities.location(entity)?;
+        let component_id = self.components.id(TypeId::of::<T>())?;
+        if !self.contains_component(entity, location, component_id) {
+            return None;
+        }
+
+        T::on_remove(self, entity);
+        let location = self.entities.location(entity)?;
+        if let Some(sparse_set) = self.sparse_sets.get_mut(&component_id) {
+            return sparse_set.take(entity);
+        }
+
+        let mut components = self.archetypes.get(location.archetype).components().clone();
+        if !components.contains(component_id) {
+            return None; // removed by the hook
         }
-        Ok(self)
+        components.remove(component_id);
+        let target = self.archetypes.get_or_insert(components, &self.components);
+        let mut taken = Column::<T>::new_boxed();
+        self.move_entity(entity, location, target, Some((component_id, taken.as_mut())));
+        let value = taken
+            .as_any_mut()
+            .downcast_mut::<Column<T>>()
+            .expect("column is created for the component type")
+            .take(0);
+        Some(value)
     }
 
-    pub fn add_component(&mut self, data: impl Any, index: usize) -> Result<(), &'static str> {
-        let type_id = data.type_id();
-        let mask = self
-            .bit_masks
-            .get(&type_id)
-            .ok_or_else(|| "Trying to add not registered component")?;
+    /// Adds every component of the bundle to the entity at once, replacing the ones it already has.
+    /// The entity moves to another archetype at most once and is left untouched when an error is returned,
+    /// like [EcsError::DuplicateComponent] for a bundle containing the same component twice.
+    pub fn add_bundle<B: Bundle>(&mut self, bundle: B, entity: Entity) -> Result<(), EcsError> {
+        self.flush_entities();
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(EcsError::NoSuchEntity(entity))?;
+        let component_ids = self.register_bundle::<B>()?;
+        let target = self.bundle_archetype(location.archetype, &component_ids);
+        if target != location.archetype {
+            self.move_entity(entity, location, target, None);
+        }
+        let location = self.entities.location(entity).expect("entity was just moved");
+        bundle.write(&mut BundleWriter::new(self, entity, location));
+        self.call_on_insert(entity, &component_ids);
+        Ok(())
+    }
 
-        self.bit_maps[index] |= mask;
-        self.components.get_mut(&type_id).unwrap()[index] = Some(Rc::new(RefCell::new(data)));
+    /// Removes every component of the bundle which the entity has, components it does not have are skipped.
+    /// [Component::on_remove()] is called for the removed components first, then the entity moves to another archetype at most once.
+    pub fn remove_bundle<B: Bundle>(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.flush_entities();
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(EcsError::NoSuchEntity(entity))?;
+        let mut component_ids = vec![];
+        B::registered_components(self, &mut component_ids);
+        component_ids.retain(|component_id| self.contains_component(entity, location, *component_id));
+        if component_ids.is_empty() {
+            return Ok(());
+        }
 
+        for component_id in &component_ids {
+            (self.components.info(*component_id).on_remove)(self, entity);
+        }
+        let Some(location) = self.entities.location(entity) else {
+            return Ok(()); // removed by one of the hooks
+        };
+
+        let mut components = self.archetypes.get(location.archetype).components().clone();
+        for component_id in component_ids {
+            match self.sparse_sets.get_mut(&component_id) {
+                Some(sparse_set) => {
+                    sparse_set.remove(entity);
+                }
+                None => components.remove(component_id),
+            }
+        }
+        let target = self.archetypes.get_or_insert(components, &self.components);
+        if target != location.archetype {
+            self.move_entity(entity, location, target, None);
+        }
         Ok(())
     }
 
-    pub fn remove_component<T: Any>(&mut self, index: usize) -> Result<(), &'static str> {
-        let type_id = TypeId::of::<T>();
-        let mask = self
-            .bit_masks
-            .get(&type_id)
-            .ok_or_else(|| "Tried to remove component from entity that does not have one!")?;
+    /// Creates a statically typed query, for example `world.query::<(&Health, &mut Speed)>()`.
+    pub fn query<D: QueryData>(&self) -> Query<'_, D> {
+        Query::new(self, self.ticks())
+    }
+
+    /// Creates a statically typed query whose entities also have to match the filter `F`,
+    /// for example `world.query_filtered::<&Health, (With<Player>, Without<Dead>)>()`.
+    pub fn query_filtered<D: QueryData, F: QueryFilter>(&self) -> Query<'_, D, F> {
+        Query::new(self, self.ticks())
+    }
+
+    /// Creates a query whose components are chosen at runtime with [DynamicQuery::with_component()].
+    pub fn dynamic_query(&self) -> DynamicQuery<'_> {
+        DynamicQuery::new(self)
+    }
 
-        if self.has_component(index, *mask) {
-            self.bit_maps[index] ^= *mask;
+    /// Makes every change done so far invisible to [Added] and [Changed] filters.
+    /// [Systems] do it on their own before every system, so this is only needed when querying outside of systems.
+    pub fn clear_trackers(&mut self) {
+        self.check_change_ticks();
+        self.last_change_tick = self.change_tick;
+        self.change_tick = self.change_tick.wrapping_add(1);
+    }
+
+    /// Creates a queue for structural changes which can be recorded while the world is borrowed, see [Commands].
+    pub fn commands(&self) -> Commands<'_, '_> {
+        Commands::new(self, None)
+    }
+
+    /// Creates the entities reserved by [Commands::spawn()] and applies every recorded command in order.
+    pub fn apply_commands(&mut self) {
+        loop {
+            self.flush_entities();
+            let queue = std::mem::take(
+                self.command_queue
+                    .get_mut()
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+            if queue.is_empty() {
+                break;
+            }
+            for command in queue {
+                command(self);
+            }
+        }
+    }
+
+    /// Adds the [Events] resource for events of type `T`, its buffers are swapped by [World::update_events()].
+    /// Does nothing if the events were already added.
+    pub fn add_event<T: Any + Send + Sync>(&mut self) {
+        if self.get_resource::<Events<T>>().is_none() {
+            self.add_resource(Events::<T>::new());
+            self.event_updaters.push(Events::<T>::update_in_world);
         }
+    }
 
-        Ok(())
+    /// Returns None if the events were not added with [World::add_event()].
+    pub fn event_writer<T: Any>(&self) -> Option<EventWriter<'_, T>> {
+        self.get_resource_mut::<Events<T>>().map(EventWriter::new)
     }
 
-    pub fn query(&self) -> Query {
-        Query::new(&self.bit_masks, &self.bit_maps, &self.components)
+    /// Calls [Events::update()] on every event resource, [Systems::update()] does it at the beginning of every frame.
+    pub fn update_events(&mut self) {
+        for updater in self.event_updaters.clone() {
+            updater(self);
+        }
     }
 
-    pub fn add_resource(&mut self, resource_data: impl Any) {
+    pub fn add_resource(&mut self, resource_data: impl Any + Send + Sync) {
         let type_id = resource_data.type_id();
-        self.resources.insert(type_id, Box::new(resource_data));
+        self.resources.insert(type_id, AtomicRefCell::new(Box::new(resource_data)));
     }
 
-    pub fn get_resource<T: Any>(&self) -> Option<&T> {
+    /// Borrows the resource, other resources can be borrowed and queries can run while the borrow is alive.
+    /// Panics with the name of the resource if it is already borrowed mutably, see [World::try_get_resource()].
+    pub fn get_resource<T: Any>(&self) -> Option<Res<'_, T>> {
+        self.try_get_resource().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Borrows the resource mutably, other resources can be borrowed and queries can run while the borrow is alive.
+    /// Panics with the name of the resource if it is already borrowed, see [World::try_get_resource_mut()].
+    pub fn get_resource_mut<T: Any>(&self) -> Option<ResMut<'_, T>> {
+        self.try_get_resource_mut().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Borrows the resource, returns an error if it is already borrowed mutably and None if it does not exist.
+    pub fn try_get_resource<T: Any>(&self) -> Result<Option<Res<'_, T>>, EcsError> {
+        let Some(data) = self.resources.get(&TypeId::of::<T>()) else {
+            return self.check_detached::<T>().map(|_| None);
+        };
+        let data = data.try_borrow().ok_or(EcsError::borrowed::<T>(BorrowState::BorrowedMutably))?;
+        Ok(Res::new(data))
+    }
+
+    /// Borrows the resource mutably, returns an error if it is already borrowed and None if it does not exist.
+    pub fn try_get_resource_mut<T: Any>(&self) -> Result<Option<ResMut<'_, T>>, EcsError> {
+        let Some(data) = self.resources.get(&TypeId::of::<T>()) else {
+            return self.check_detached::<T>().map(|_| None);
+        };
+        let data = data.try_borrow_mut().ok_or(EcsError::borrowed::<T>(BorrowState::Borrowed))?;
+        Ok(ResMut::new(data))
+    }
+
+    /// Takes the resource out of the world for the duration of `f`, so it can be used together with the world,
+    /// and puts it back afterwards, also when `f` panics. Returns [EcsError::MissingResource] if the resource does not exist.
+    ///
+    /// Borrowing the resource inside `f`, also with a nested scope, fails with [BorrowState::Detached].
+    /// Adding a resource of the same type inside `f` has no effect, it is replaced by the one put back.
+    ///
+    /// Example:
+    /// ```
+    /// use wgtr_ecs::*;
+    /// struct Score(u32);
+    /// #[derive(Component)]
+    /// struct Coin;
+    ///
+    /// let mut world = World::new();
+    /// world.register_component::<Coin>();
+    /// world.create_entity().with_component(Coin).unwrap();
+    /// world.add_resource(Score(0));
+    ///
+    /// world.resource_scope(|world, score: &mut Score| {
+    ///     score.0 += world.query::<&Coin>().run().len() as u32;
+    ///     world.create_entity().with_component(Coin).unwrap();
+    ///     assert!(world.try_get_resource::<Score>().is_err());
+    /// }).unwrap();
+    /// assert_eq!(world.get_resource::<Score>().unwrap().0, 1);
+    /// ```
+    pub fn resource_scope<T: Any + Send + Sync, R>(
+        &mut self,
+        f: impl FnOnce(&mut World, &mut T) -> R,
+    ) -> Result<R, EcsError> {
         let type_id = TypeId::of::<T>();
-        if let Some(data) = self.resources.get(&type_id) {
-            return data.downcast_ref();
+        self.check_detached::<T>()?;
+        let mut resource = self.resources.remove(&type_id).ok_or(EcsError::MissingResource {
+            type_name: type_name::<T>(),
+        })?;
+        self.detached_resources.insert(type_id);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let value = resource
+                .get_mut()
+                .downcast_mut()
+                .expect("resources are stored under the id of their type");
+            f(self, value)
+        }));
+        self.detached_resources.remove(&type_id);
+        self.resources.insert(type_id, resource);
+        match result {
+            Ok(result) => Ok(result),
+            Err(payload) => panic::resume_unwind(payload),
         }
-        None
     }
 
-    pub fn get_resource_mut<T: Any>(&mut self) -> Option<&mut T> {
-        let type_id = TypeId::of::<T>();
-        if let Some(data) = self.resources.get_mut(&type_id) {
-            return data.downcast_mut();
+    fn check_detached<T: Any>(&self) -> Result<(), EcsError> {
+        match self.detached_resources.contains(&TypeId::of::<T>()) {
+            true => Err(EcsError::borrowed::<T>(BorrowState::Detached)),
+            false => Ok(()),
         }
-        None
     }
 
     pub fn remove_resource<T: Any>(&mut self) {
@@ -157,14 +553,169 @@ impl World {
         self.resources.remove(&type_id);
     }
 
-    fn has_component(&self, index: usize, mask: u128) -> bool {
-        self.bit_maps[index] & mask == mask
+    /// Adds a resource which is not `Send`, like a window or a graphics context. It can only be used on the current thread,
+    /// accessing it from any other thread panics. Systems using it through [NonSend] run on the thread calling [Systems::update()].
+    pub fn add_non_send_resource(&mut self, resource_data: impl Any) {
+        let type_id = resource_data.type_id();
+        self.non_send_resources.insert(type_id, NonSendCell::new(Box::new(resource_data)));
+    }
+
+    /// Panics if called from another thread than the one which added the resource.
+    pub fn get_non_send_resource<T: Any>(&self) -> Option<NonSend<'_, T>> {
+        let data = self.non_send_resources.get(&TypeId::of::<T>())?;
+        NonSend::new(data.get::<T>().borrow())
+    }
+
+    /// Panics if called from another thread than the one which added the resource.
+    pub fn get_non_send_resource_mut<T: Any>(&mut self) -> Option<&mut T> {
+        let data = self.non_send_resources.get_mut(&TypeId::of::<T>())?;
+        data.get_mut::<T>().downcast_mut()
+    }
+
+    /// Borrows the non-Send resource mutably through a shared reference, panics if it is already borrowed or used on another thread.
+    pub(crate) fn borrow_non_send_resource_mut<T: Any>(&self) -> Option<NonSendMut<'_, T>> {
+        let data = self.non_send_resources.get(&TypeId::of::<T>())?;
+        NonSendMut::new(data.get::<T>().borrow_mut())
+    }
+
+    /// Removing is allowed from any thread, the resource is leaked if it is not the thread which added it.
+    pub fn remove_non_send_resource<T: Any>(&mut self) {
+        self.non_send_resources.remove(&TypeId::of::<T>());
+    }
+
+    /// Registers the components of the bundle and returns their ids.
+    /// Returns [EcsError::DuplicateComponent] if one of them repeats.
+    fn register_bundle<B: Bundle>(&mut self) -> Result<Vec<usize>, EcsError> {
+        let mut component_ids = vec![];
+        B::register_components(self, &mut component_ids);
+        let mut sorted = component_ids.clone();
+        sorted.sort_unstable();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(EcsError::DuplicateComponent {
+                type_name: self.components.info(pair[0]).type_name,
+            });
+        }
+        Ok(component_ids)
+    }
+
+    /// Spawns the bundle into the `target` archetype returned by [World::bundle_archetype()] for its components.
+    fn spawn_in<B: Bundle>(&mut self, bundle: B, target: usize, component_ids: &[usize]) -> Entity {
+        self.flush_entities(); // hooks of the previous entity in a batch could have reserved some
+        let archetype = self.archetypes.get_mut(target);
+        let location = EntityLocation {
+            archetype: target,
+            row: archetype.len(),
+        };
+        let entity = self.entities.alloc(location);
+        archetype.push_entity(entity);
+        bundle.write(&mut BundleWriter::new(self, entity, location));
+        self.call_on_insert(entity, component_ids);
+        entity
+    }
+
+    /// Archetype of entities from the `source` archetype after adding the components, sparse set components do not change it.
+    fn bundle_archetype(&mut self, source: usize, component_ids: &[usize]) -> usize {
+        let mut components = self.archetypes.get(source).components().clone();
+        for component_id in component_ids {
+            if !self.sparse_sets.contains_key(component_id) {
+                components.insert(*component_id);
+            }
+        }
+        self.archetypes.get_or_insert(components, &self.components)
+    }
+
+    fn call_on_insert(&mut self, entity: Entity, component_ids: &[usize]) {
+        for component_id in component_ids {
+            (self.components.info(*component_id).on_insert)(self, entity);
+        }
+    }
+
+    /// Returns true if the living entity at `location` has the component, whatever its storage.
+    fn contains_component(&self, entity: Entity, location: EntityLocation, component_id: usize) -> bool {
+        match self.sparse_sets.get(&component_id) {
+            Some(sparse_set) => sparse_set.contains(entity),
+            None => self.archetypes.get(location.archetype).components().contains(component_id),
+        }
+    }
+
+    /// Id of the component, registering it with the storage if it is not registered yet.
+    fn init_component<T: Component>(&mut self, storage: StorageType) -> usize {
+        if let Some(component_id) = self.components.id(TypeId::of::<T>()) {
+            return component_id;
+        }
+        let component_id = self.components.register::<T>(storage);
+        if storage == StorageType::SparseSet {
+            let dense = (self.components.info(component_id).new_column)();
+            self.sparse_sets.insert(component_id, SparseSet::new(dense));
+        }
+        component_id
+    }
+
+    /// Ticks used by queries created outside of parallel systems.
+    /// Clamps component ticks so old that they would look new once the tick counter wraps around.
+    /// Only does the work once every `CHECK_TICK_THRESHOLD` ticks, returns true if it did.
+    /// Called by [Systems::update()] and [World::clear_trackers()].
+    pub(crate) fn check_change_ticks(&mut self) -> bool {
+        let change_tick = self.change_tick;
+        if change_tick.wrapping_sub(self.last_check_tick) < CHECK_TICK_THRESHOLD {
+            return false;
+        }
+        for archetype in self.archetypes.iter_mut() {
+            archetype.check_change_ticks(change_tick);
+        }
+        for sparse_set in self.sparse_sets.values_mut() {
+            sparse_set.check_change_ticks(change_tick);
+        }
+        clamp_tick(&mut self.last_change_tick, change_tick);
+        self.last_check_tick = change_tick;
+        true
+    }
+
+    pub(crate) fn ticks(&self) -> SystemTicks {
+        SystemTicks {
+            last_run: self.last_change_tick,
+            this_run: self.change_tick,
+        }
+    }
+
+    /// Creates empty entities for handles reserved by [Commands::spawn()].
+    fn flush_entities(&mut self) {
+        for entity in self.entities.flush() {
+            let archetype = self.archetypes.get_mut(Archetypes::EMPTY);
+            let location = EntityLocation {
+                archetype: Archetypes::EMPTY,
+                row: archetype.len(),
+            };
+            archetype.push_entity(entity);
+            self.entities.set_location(entity, location);
+        }
+    }
+
+    /// Moves the entity into the `target` archetype, keeping only the components `target` has columns for.
+    /// The component with the id of `taken` is pushed into its column instead of being dropped.
+    fn move_entity(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        target: usize,
+        taken: Option<(usize, &mut dyn AnyColumn)>,
+    ) {
+        let (from, to) = self.archetypes.get_two_mut(location.archetype, target);
+        let swapped = from.move_row(location.row, to, taken);
+        let new_location = EntityLocation {
+            archetype: target,
+            row: to.len() - 1,
+        };
+        if let Some(swapped) = swapped {
+            self.entities.set_location(swapped, location);
+        }
+        self.entities.set_location(entity, new_location);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::{any::TypeId, cell::Ref};
+    use std::any::TypeId;
 
     use crate::*;
 
@@ -173,79 +724,149 @@ mod test {
         let mut world = World::new();
         world.register_component::<Health>();
         let type_id = TypeId::of::<Health>();
-        let health_components = world.components.get(&type_id).unwrap();
-        assert_eq!(health_components.len(), 0);
+        assert!(world.components.id(type_id).is_some());
+        assert_eq!(world.components.len(), 1);
     }
 
     #[test]
-    fn bitmask_updated_when_registering_component() {
+    fn registering_again_keeps_storage() -> Result<(), EcsError> {
+        let mut world = World::new();
+        world.register_component_with_storage::<Health>(StorageType::SparseSet)?;
+        let entity = world.create_entity().with_component(Health(10))?.id();
+
+        world.register_component::<Health>();
+        world.register_component_with_storage::<Health>(StorageType::SparseSet)?;
+        assert_eq!(
+            world.register_component_with_storage::<Health>(StorageType::Table),
+            Err(EcsError::StorageConflict {
+                type_name: type_name::<Health>(),
+                storage: StorageType::SparseSet
+            })
+        );
+        assert_eq!(world.components.len(), 1);
+        assert!(world.sparse_sets[&0].contains(entity));
+        assert_eq!(world.query::<&Health>().get(entity)?.0, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn derived_storage_is_used_on_first_insert() -> Result<(), EcsError> {
+        #[derive(Component)]
+        #[component(storage = "sparse_set")]
+        struct Stunned;
+
+        let mut world = World::new();
+        let entity = world.create_entity().with_component(Stunned)?.id();
+        assert_eq!(world.components.info(0).storage, StorageType::SparseSet);
+        assert!(world.sparse_sets[&0].contains(entity));
+        assert_eq!(world.entities.location(entity).unwrap().archetype, Archetypes::EMPTY);
+        Ok(())
+    }
+
+    #[test]
+    fn bundles_move_entity_once() -> Result<(), EcsError> {
+        let mut world = World::new();
+        let entity = world.spawn((Health(100), Speed(10)));
+        assert_eq!(world.archetypes.iter().count(), 2); // empty archetype and the one of the bundle
+        assert_ne!(world.entities.location(entity).unwrap().archetype, Archetypes::EMPTY);
+
+        let entity = world.create_entity().id();
+        world.add_bundle((Speed(5), Health(50)), entity)?;
+        assert_eq!(world.archetypes.iter().count(), 2);
+        world.remove_bundle::<(Health, Speed)>(entity)?;
+        assert_eq!(world.entities.location(entity).unwrap().archetype, Archetypes::EMPTY);
+        assert_eq!(world.archetypes.iter().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn bit_assigned_when_registering_component() {
         let mut world = World::new();
 
         world.register_component::<Health>();
         let type_id = TypeId::of::<Health>();
-        let mask = world.bit_masks.get(&type_id).unwrap();
-        assert_eq!(*mask, 1);
+        assert_eq!(world.components.id(type_id), Some(0));
 
         world.register_component::<Speed>();
         let type_id = TypeId::of::<Speed>();
-        let mask = world.bit_masks.get(&type_id).unwrap();
-        assert_eq!(*mask, 2);
+        assert_eq!(world.components.id(type_id), Some(1));
     }
 
     #[test]
-    fn bitmap_is_updated_when_creating_entities() -> Result<(), &'static str> {
+    fn entity_moves_between_archetypes() -> Result<(), EcsError> {
         let mut world = World::new();
         world.register_component::<Health>();
         world.register_component::<Speed>();
 
-        world
+        let first = world
             .create_entity()
             .with_component(Health(100))?
-            .with_component(Speed(10))?;
+            .with_component(Speed(10))?
+            .id();
+
+        let location = world.entities.location(first).unwrap();
+        let archetype = world.archetypes.get(location.archetype);
+        assert!(archetype.components().contains(0));
+        assert!(archetype.components().contains(1));
+        assert_eq!(archetype.entities(), &[first]);
 
-        let entity_map = world.bit_maps[0];
-        assert_eq!(entity_map, 3); // 0b001 + 0b010 = 0b011 = 1 + 2 = 3
+        let second = world.create_entity().with_component(Speed(10))?.id();
 
-        world.create_entity().with_component(Speed(10))?;
+        let location = world.entities.location(second).unwrap();
+        let archetype = world.archetypes.get(location.archetype);
+        assert!(!archetype.components().contains(0));
+        assert!(archetype.components().contains(1));
 
-        let entity_map = world.bit_maps[1];
-        assert_eq!(entity_map, 2);
+        world.remove_component::<Health>(first)?;
+        assert_eq!(world.entities.location(first).unwrap().archetype, location.archetype);
+        let archetype = world.archetypes.get(location.archetype);
+        assert_eq!(archetype.len(), 2);
+        let speed = archetype.typed_column::<Speed>(1).unwrap().get(0).unwrap();
+        assert_eq!(speed.borrow().0, 10);
 
         Ok(())
     }
 
     #[test]
-    fn create_entity_in_free_spot() -> Result<(), &'static str> {
+    fn sparse_component_does_not_move_entity() -> Result<(), EcsError> {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_component_with_storage::<Speed>(StorageType::SparseSet)?;
+
+        let entity = world.create_entity().with_component(Health(100))?.id();
+        let location = world.entities.location(entity).unwrap();
+
+        world.add_component(Speed(10), entity)?;
+        assert_eq!(world.entities.location(entity), Some(location));
+        assert!(world.sparse_sets[&1].contains(entity));
+
+        world.remove_component::<Speed>(entity)?;
+        assert_eq!(world.entities.location(entity), Some(location));
+        assert!(!world.sparse_sets[&1].contains(entity));
+        Ok(())
+    }
+
+    #[test]
+    fn create_entity_in_free_spot() -> Result<(), EcsError> {
         let mut world = World::new();
         world.register_component::<Health>();
         world.register_component::<Speed>();
 
-        world
-            .create_entity()
-            .with_component(Health(100))?
-            .with_component(Speed(10))?;
-        world
-            .create_entity()
-            .with_component(Health(100))?
-            .with_component(Speed(10))?;
-        world
-            .create_entity()
-            .with_component(Health(100))?
-            .with_component(Speed(10))?;
-        world
-            .create_entity()
-            .with_component(Health(100))?
-            .with_component(Speed(10))?;
-        world
-            .create_entity()
-            .with_component(Health(100))?
-            .with_component(Speed(10))?;
+        let mut entities = vec![];
+        for _ in 0..5 {
+            let entity = world
+                .create_entity()
+                .with_component(Health(100))?
+                .with_component(Speed(10))?
+                .id();
+            entities.push(entity);
+        }
 
-        world.remove_entity(1)?;
-        world.remove_entity(3)?;
+        world.remove_entity(entities[1])?;
+        world.remove_entity(entities[3])?;
 
         let query = world
-            .query()
+            .dynamic_query()
             .with_component::<Health>()?
             .with_component::<Speed>()?
             .run();
@@ -259,24 +880,94 @@ mod test {
             .create_entity()
             .with_component(Health(500))?
             .with_component(Speed(10))?;
-        let query = world.query().with_component::<Health>()?.run();
+        let query = world.dynamic_query().with_component::<Health>()?.run();
         assert_eq!(query.0.len(), 6);
-        assert_eq!(query.0[1], 1);
         let undone_healths = &query.1[0];
-        let healths: Vec<Ref<dyn Any>> = undone_healths.iter().map(|e| e.borrow()).collect();
-        let deref_healths: Vec<&Health> = healths
+        let healths: Vec<AtomicRef<dyn Any>> = undone_healths.iter().map(|e| e.borrow()).collect();
+        let deref_healths: Vec<(usize, u32)> = query
+            .0
             .iter()
-            .map(|e| e.downcast_ref::<Health>().unwrap())
+            .zip(&healths)
+            .map(|(entity, health)| (entity.index(), health.downcast_ref::<Health>().unwrap().0))
             .collect();
-        assert_eq!(deref_healths[3].0, 300); // first free slot
-        assert_eq!(deref_healths[1].0, 400); // second free slot
-        assert_eq!(deref_healths[5].0, 500); // no free slots
-        assert_eq!(deref_healths[2].0, 100); // untouched
-                                                //assert_eq!(health.0, 300);
+        assert!(deref_healths.contains(&(3, 300))); // first free slot
+        assert!(deref_healths.contains(&(1, 400))); // second free slot
+        assert!(deref_healths.contains(&(5, 500))); // no free slots
+        assert!(deref_healths.contains(&(2, 100))); // untouched
         Ok(())
     }
-    #[derive(Debug)]
+    #[derive(Debug, Component)]
     struct Health(pub u32);
+    #[allow(dead_code)]
+    #[derive(Component)]
     struct Speed(pub u32);
-}
 
+    #[test]
+    fn world_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<World>();
+        assert_send::<Systems>();
+    }
+
+    #[test]
+    fn reserved_entities_take_free_spots_first() {
+        let mut world = World::new();
+        let first = world.create_entity().id();
+        let second = world.create_entity().id();
+        world.remove_entity(first).unwrap();
+        world.remove_entity(second).unwrap();
+
+        let reserved: Vec<Entity> = (0..3).map(|_| world.entities.reserve()).collect();
+        assert_eq!(reserved[0].index(), second.index());
+        assert_eq!(reserved[1].index(), first.index());
+        assert_eq!(reserved[2].index(), 2);
+
+        world.apply_commands();
+        assert!(reserved.iter().all(|entity| world.is_alive(*entity)));
+        assert_eq!(world.archetypes.get(Archetypes::EMPTY).len(), 3);
+    }
+
+    #[test]
+    fn old_changes_stay_old_when_ticks_wrap_around() {
+        let mut world = World::new();
+        let entity = world.spawn(Health(1));
+        world.clear_trackers();
+
+        for _ in 0..8 {
+            world.change_tick = world.change_tick.wrapping_add(change_detection::CHECK_TICK_THRESHOLD);
+            world.clear_trackers();
+            assert!(world.query_filtered::<Entity, Added<Health>>().run().is_empty());
+        }
+        // without clamping the component added at tick 1 would look new once the counter wraps around to 1
+        world.change_tick = u32::MAX;
+        world.clear_trackers();
+        world.clear_trackers();
+        assert_eq!(world.change_tick, 1);
+        assert!(world.query_filtered::<Entity, Added<Health>>().run().is_empty());
+        world.query::<&mut Health>().get(entity).unwrap().0 += 1;
+        assert_eq!(world.query_filtered::<Entity, Changed<Health>>().run(), [entity]);
+    }
+
+    #[test]
+    fn systems_clamp_their_last_runs() {
+        fn count_added(query: Query<Entity, Added<Health>>, mut seen: ResMut<Vec<usize>>) {
+            seen.push(query.run().len());
+        }
+
+        let mut world = World::new();
+        world.add_resource(Vec::<usize>::new());
+        world.spawn(Health(1));
+        let mut systems = Systems::new();
+        systems.with_system(count_added);
+
+        systems.update(&mut world);
+        for _ in 0..10 {
+            world.change_tick = world.change_tick.wrapping_add(change_detection::CHECK_TICK_THRESHOLD);
+            systems.update(&mut world);
+        }
+        world.spawn(Health(2));
+        systems.update(&mut world);
+        assert_eq!(*world.get_resource::<Vec<usize>>().unwrap(), [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+}
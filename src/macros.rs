@@ -1,45 +1,50 @@
-/// Macro which helps with getting components when querying with [crate::Query::run_entity()]. It does unwrap an error when attempting to use invalid component for exapmle unregistered one or unqueried one.
+/// Macro which helps with getting components when querying with [crate::DynamicQuery::run_entity()]. It does unwrap an error when attempting to use invalid component for exapmle one the entity does not have.
 /// We will use get_component!(entity & TYPE); for to get immutable reference and get_component!(entity &mut TYPE); to get mutable one.
 /// 
 /// Example:
 /// ```
 /// use wgtr_ecs::get_component;
 /// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Speed(f32);
 /// 
 /// let mut world = World::new();
-/// world.register_component::<u32>();
-/// world.register_component::<f32>();
-/// world.create_entity()
-///     .with_component(100_u32).unwrap() // we registered our component before so nothing can go wrong
-///     .with_component(10.0_f32).unwrap(); // although I encourage to use with_component(10.0_f32)?; and returning an result
+/// world.register_component::<Health>();
+/// world.register_component::<Speed>();
+/// let id = world.create_entity()
+///     .with_component(Health(100)).unwrap() // we registered our component before so nothing can go wrong
+///     .with_component(Speed(10.0)).unwrap() // although I encourage to use with_component(Speed(10.0))?; and returning an result
+///     .id();
 ///
-/// let mut query = world.query();
+/// let mut query = world.dynamic_query();
 /// let entities: Vec<QueryEntity> = query
-///     .with_component::<u32>().unwrap() // same as before; 
-///     .with_component::<f32>().unwrap()
+///     .with_component::<Health>().unwrap() // same as before; 
+///     .with_component::<Speed>().unwrap()
 ///     .run_entity(); 
 ///
 /// assert_eq!(entities.len(), 1);
 ///
 /// for entity in entities {
-///     assert_eq!(entity.id, 0);
-///     let health: std::cell::Ref<u32> = get_component!(entity, &u32); // of course you don't have to specify the type, it is here only for clarity
-///     let mut speed: std::cell::RefMut<f32> = get_component!(entity, &mut f32);
-///     *speed += 2.0;
-///     assert_eq!(*health, 100);
-///     assert_eq!(*speed, 12.0);
+///     assert_eq!(entity.id, id);
+///     let health: AtomicRef<Health> = get_component!(entity, &Health); // of course you don't have to specify the type, it is here only for clarity
+///     let mut speed: AtomicRefMut<Speed> = get_component!(entity, &mut Speed);
+///     speed.0 += 2.0;
+///     assert_eq!(health.0, 100);
+///     assert_eq!(speed.0, 12.0);
 /// }
 /// ```
 #[macro_export]
 macro_rules! get_component {
     ($entity:ident, & $x:ty) => {
-        || -> std::cell::Ref<$x> {
+        || -> $crate::AtomicRef<$x> {
             let ent = &$entity;
             return ent.get_component::<$x>().unwrap();
         }()
     };
     ($entity:ident, &mut $x:ty) => {
-        || -> std::cell::RefMut<$x> {
+        || -> $crate::AtomicRefMut<$x> {
             let ent = &$entity;
             return ent.get_component_mut::<$x>().unwrap();
         }()
@@ -47,25 +52,31 @@ macro_rules! get_component {
 }
 
 
-/// Macro which helps registering all components in a query. If component was not registered before it will panic.
+/// Macro which adds all components to a dynamic query with [crate::DynamicQuery::with_component()].
+/// A component which was never registered nor inserted makes the query match nothing.
 ///
 /// Example:
 /// ```
 /// use wgtr_ecs::*; // for macros
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Speed(f32);
+///
 /// let mut world = World::new();
 ///
-/// world.register_component::<u32>();
-/// world.register_component::<f32>();
+/// world.register_component::<Health>();
+/// world.register_component::<Speed>();
 ///
 /// world.create_entity()
-///     .with_component(10u32).unwrap()
-///     .with_component(10.1f32).unwrap();
+///     .with_component(Health(10)).unwrap()
+///     .with_component(Speed(10.1)).unwrap();
 ///
-/// let mut query = world.query();
-/// make_query!(query, u32, f32);
+/// let mut query = world.dynamic_query();
+/// make_query!(query, Health, Speed);
 /// for entity in query.run_entity(){
-///     let mut f = get_component!(entity, &mut f32);
-///     *f += 1.0;
+///     let mut speed = get_component!(entity, &mut Speed);
+///     speed.0 += 1.0;
 /// }
 ///
 /// ```
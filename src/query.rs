@@ -1,204 +1,972 @@
-use std::{
-    any::{Any, TypeId},
-    collections::HashMap, cell::RefCell, rc::Rc,
+use std::{any::TypeId, marker::PhantomData, slice};
+
+use crate::{
+    archetype::Archetype,
+    column::{ColumnBorrow, ComponentFetch},
+    sparse_set::SparseSet,
+    Access, AtomicRef, AtomicRefMut, BorrowState, Component, EcsError, Entity, SystemTicks, World,
 };
 
-use crate::QueryEntity;
+/// Shared part of [QueryData] and [QueryFilter]: deciding which entities match.
+pub trait WorldQuery {
+    #[doc(hidden)]
+    type State;
+    #[doc(hidden)]
+    type Fetch<'w>;
+
+    #[doc(hidden)]
+    fn init_state(world: &World) -> Self::State;
+
+    /// Adds the components the query borrows, filters which only check if a component exists add nothing.
+    #[doc(hidden)]
+    fn access(access: &mut Access);
+
+    /// Resolves the storage of the archetype. Returns None if no entity of the archetype can match.
+    #[doc(hidden)]
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>>;
+
+    /// Checks per entity requirements which the archetype alone can not answer, like sparse set components.
+    #[doc(hidden)]
+    fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool;
+}
+
+/// Data which can be fetched for every entity matched by a [Query].
+///
+/// It is implemented for `&T` (yields [AtomicRef]), `&mut T` (yields [AtomicRefMut]), `Option<D>`, [Entity] (yields the handle itself)
+/// and tuples of those. An entity matches the query when it has every requested component.
+pub trait QueryData: WorldQuery {
+    type Item<'w>;
+
+    /// Borrows the data of a matched entity, fails if it is already borrowed in a conflicting way.
+    #[doc(hidden)]
+    fn fetch<'w>(fetch: &Self::Fetch<'w>, row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError>;
+
+    /// Borrows every column the query reads or writes in the archetype at once, see [Query::for_each()].
+    /// Returns false if one of them is already borrowed in a conflicting way.
+    #[doc(hidden)]
+    fn borrow_columns<'w>(fetch: &Self::Fetch<'w>, borrows: &mut Vec<ColumnBorrow<'w>>) -> bool;
+
+    /// Same as [QueryData::fetch()] without borrowing each component.
+    ///
+    /// # Safety
+    /// [QueryData::borrow_columns()] has to succeed for the fetch before and its borrows have to outlive the item.
+    /// Every row can be fetched at most once while they are alive.
+    #[doc(hidden)]
+    unsafe fn fetch_unchecked<'w>(
+        fetch: &Self::Fetch<'w>,
+        row: usize,
+        entity: Entity,
+    ) -> Result<Self::Item<'w>, EcsError>;
+}
+
+/// [QueryData] which only reads components, so [Query::iter()] can hand out its items through a shared reference.
+///
+/// Implemented for `&T`, [Entity], `Option<D>` and tuples of those.
+///
+/// ```compile_fail
+/// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+///
+/// let world = World::new();
+/// world.query::<&mut Health>().iter(); // needs iter_mut()
+/// ```
+pub trait ReadOnlyQueryData: QueryData {}
+
+/// Filters which only decide if an entity matches a [Query] without fetching anything:
+/// [With], [Without], [Or] and tuples of them (all of them have to match).
+pub trait QueryFilter: WorldQuery {}
+
+impl<T: Component> WorldQuery for &T {
+    type State = Option<usize>; // None if the component is not registered
+    type Fetch<'w> = ComponentFetch<'w, T>;
+
+    fn init_state(world: &World) -> Self::State {
+        world.components.id(TypeId::of::<T>())
+    }
+
+    fn access(access: &mut Access) {
+        access.read_component::<T>();
+    }
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        _ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        ComponentFetch::new((*state)?, world, archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, _row: usize, entity: Entity) -> bool {
+        fetch.contains(entity)
+    }
+}
+
+impl<T: Component> QueryData for &T {
+    type Item<'w> = AtomicRef<'w, T>;
+
+    fn fetch<'w>(fetch: &Self::Fetch<'w>, row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError> {
+        fetch
+            .get(row, entity)
+            .ok_or(EcsError::missing::<T>(entity))?
+            .try_borrow()
+            .ok_or(EcsError::borrowed::<T>(BorrowState::BorrowedMutably))
+    }
+
+    fn borrow_columns<'w>(fetch: &Self::Fetch<'w>, borrows: &mut Vec<ColumnBorrow<'w>>) -> bool {
+        fetch.borrow_column(false).map(|borrow| borrows.push(borrow)).is_some()
+    }
+
+    unsafe fn fetch_unchecked<'w>(
+        fetch: &Self::Fetch<'w>,
+        row: usize,
+        entity: Entity,
+    ) -> Result<Self::Item<'w>, EcsError> {
+        let cell = fetch.get(row, entity).ok_or(EcsError::missing::<T>(entity))?;
+        // SAFETY: the column is borrowed, see fetch_unchecked
+        Ok(unsafe { cell.borrow_unchecked() })
+    }
+}
+
+impl<T: Component> ReadOnlyQueryData for &T {}
+
+impl<T: Component> WorldQuery for &mut T {
+    type State = Option<usize>;
+    type Fetch<'w> = (ComponentFetch<'w, T>, u32); // storage and the tick to mark fetched components as changed at
+
+    fn init_state(world: &World) -> Self::State {
+        <&T>::init_state(world)
+    }
+
+    fn access(access: &mut Access) {
+        access.write_component::<T>();
+    }
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        let fetch = <&T>::init_fetch(state, world, archetype, ticks)?;
+        Some((fetch, ticks.this_run))
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+        <&T>::matches(&fetch.0, row, entity)
+    }
+}
+
+/// Fetching a component mutably marks it as changed, see [crate::Changed].
+impl<T: Component> QueryData for &mut T {
+    type Item<'w> = AtomicRefMut<'w, T>;
+
+    fn fetch<'w>(fetch: &Self::Fetch<'w>, row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError> {
+        let (component, tick) = fetch;
+        let component_mut = component
+            .get(row, entity)
+            .ok_or(EcsError::missing::<T>(entity))?
+            .try_borrow_mut()
+            .ok_or(EcsError::borrowed::<T>(BorrowState::Borrowed))?;
+        if let Some(ticks) = component.ticks(row, entity) {
+            ticks.set_changed(*tick);
+        }
+        Ok(component_mut)
+    }
+
+    fn borrow_columns<'w>(fetch: &Self::Fetch<'w>, borrows: &mut Vec<ColumnBorrow<'w>>) -> bool {
+        fetch.0.borrow_column(true).map(|borrow| borrows.push(borrow)).is_some()
+    }
+
+    unsafe fn fetch_unchecked<'w>(
+        fetch: &Self::Fetch<'w>,
+        row: usize,
+        entity: Entity,
+    ) -> Result<Self::Item<'w>, EcsError> {
+        let (component, tick) = fetch;
+        let cell = component.get(row, entity).ok_or(EcsError::missing::<T>(entity))?;
+        // SAFETY: the column is borrowed mutably and the row is fetched once, see fetch_unchecked
+        let component_mut = unsafe { cell.borrow_mut_unchecked() };
+        if let Some(ticks) = component.ticks(row, entity) {
+            ticks.set_changed(*tick);
+        }
+        Ok(component_mut)
+    }
+}
+
+impl WorldQuery for Entity {
+    type State = ();
+    type Fetch<'w> = ();
+
+    fn init_state(_world: &World) -> Self::State {}
+
+    fn access(_access: &mut Access) {}
+
+    fn init_fetch<'w>(
+        _state: &Self::State,
+        _world: &'w World,
+        _archetype: &'w Archetype,
+        _ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        Some(())
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _row: usize, _entity: Entity) -> bool {
+        true
+    }
+}
+
+impl QueryData for Entity {
+    type Item<'w> = Entity;
+
+    fn fetch<'w>(_fetch: &Self::Fetch<'w>, _row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError> {
+        Ok(entity)
+    }
+
+    fn borrow_columns<'w>(_fetch: &Self::Fetch<'w>, _borrows: &mut Vec<ColumnBorrow<'w>>) -> bool {
+        true
+    }
+
+    unsafe fn fetch_unchecked<'w>(
+        _fetch: &Self::Fetch<'w>,
+        _row: usize,
+        entity: Entity,
+    ) -> Result<Self::Item<'w>, EcsError> {
+        Ok(entity)
+    }
+}
+
+impl ReadOnlyQueryData for Entity {}
+
+/// Fetches `Some` for entities which match `D` and `None` for all the others, so it never filters anything out.
+impl<D: QueryData> WorldQuery for Option<D> {
+    type State = D::State;
+    type Fetch<'w> = Option<D::Fetch<'w>>;
+
+    fn init_state(world: &World) -> Self::State {
+        D::init_state(world)
+    }
+
+    fn access(access: &mut Access) {
+        D::access(access);
+    }
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        Some(D::init_fetch(state, world, archetype, ticks))
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _row: usize, _entity: Entity) -> bool {
+        true
+    }
+}
+
+impl<D: QueryData> QueryData for Option<D> {
+    type Item<'w> = Option<D::Item<'w>>;
+
+    fn fetch<'w>(fetch: &Self::Fetch<'w>, row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError> {
+        fetch
+            .as_ref()
+            .filter(|fetch| D::matches(fetch, row, entity))
+            .map(|fetch| D::fetch(fetch, row, entity))
+            .transpose()
+    }
+
+    fn borrow_columns<'w>(fetch: &Self::Fetch<'w>, borrows: &mut Vec<ColumnBorrow<'w>>) -> bool {
+        fetch.as_ref().is_none_or(|fetch| D::borrow_columns(fetch, borrows))
+    }
+
+    unsafe fn fetch_unchecked<'w>(
+        fetch: &Self::Fetch<'w>,
+        row: usize,
+        entity: Entity,
+    ) -> Result<Self::Item<'w>, EcsError> {
+        fetch
+            .as_ref()
+            .filter(|fetch| D::matches(fetch, row, entity))
+            // SAFETY: forwarded from the caller
+            .map(|fetch| unsafe { D::fetch_unchecked(fetch, row, entity) })
+            .transpose()
+    }
+}
+
+impl<D: ReadOnlyQueryData> ReadOnlyQueryData for Option<D> {}
+
+/// Filter matching entities which have the component `T`, without borrowing it.
+pub struct With<T>(PhantomData<T>);
+
+impl<T: Component> WorldQuery for With<T> {
+    type State = Option<usize>;
+    type Fetch<'w> = ComponentFetch<'w, T>;
+
+    fn init_state(world: &World) -> Self::State {
+        <&T>::init_state(world)
+    }
+
+    fn access(_access: &mut Access) {}
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        <&T>::init_fetch(state, world, archetype, ticks)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+        <&T>::matches(fetch, row, entity)
+    }
+}
+
+impl<T: Component> QueryFilter for With<T> {}
+
+/// Filter matching entities which do not have the component `T`.
+pub struct Without<T>(PhantomData<T>);
+
+impl<T: Component> WorldQuery for Without<T> {
+    type State = Option<usize>;
+    type Fetch<'w> = Option<&'w SparseSet>; // only sparse set components have to be checked per entity
+
+    fn init_state(world: &World) -> Self::State {
+        <&T>::init_state(world)
+    }
+
+    fn access(_access: &mut Access) {}
+
+    fn init_fetch<'w>(
+        state: &Self::State,
+        world: &'w World,
+        archetype: &'w Archetype,
+        _ticks: SystemTicks,
+    ) -> Option<Self::Fetch<'w>> {
+        let Some(component_id) = state else {
+            return Some(None);
+        };
+        if let Some(sparse_set) = world.sparse_sets.get(component_id) {
+            return Some(Some(sparse_set));
+        }
+        (!archetype.components().contains(*component_id)).then_some(None)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, _row: usize, entity: Entity) -> bool {
+        fetch.is_none_or(|sparse_set| !sparse_set.contains(entity))
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {}
+
+/// Filter matching entities which match at least one of the filters in the tuple, for example `Or<(With<A>, With<B>)>`.
+pub struct Or<T>(PhantomData<T>);
+
+macro_rules! impl_world_query_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type State = ($($name::State,)*);
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn init_state(world: &World) -> Self::State {
+                ($($name::init_state(world),)*)
+            }
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn init_fetch<'w>(
+                state: &Self::State,
+                world: &'w World,
+                archetype: &'w Archetype,
+                ticks: SystemTicks,
+            ) -> Option<Self::Fetch<'w>> {
+                let ($($name,)*) = state;
+                Some(($($name::init_fetch($name, world, archetype, ticks)?,)*))
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+                let ($($name,)*) = fetch;
+                true $(&& $name::matches($name, row, entity))*
+            }
+        }
+
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($name: QueryData),*> QueryData for ($($name,)*) {
+            type Item<'w> = ($($name::Item<'w>,)*);
+
+            fn fetch<'w>(fetch: &Self::Fetch<'w>, row: usize, entity: Entity) -> Result<Self::Item<'w>, EcsError> {
+                let ($($name,)*) = fetch;
+                Ok(($($name::fetch($name, row, entity)?,)*))
+            }
+
+            fn borrow_columns<'w>(fetch: &Self::Fetch<'w>, borrows: &mut Vec<ColumnBorrow<'w>>) -> bool {
+                let ($($name,)*) = fetch;
+                true $(&& $name::borrow_columns($name, borrows))*
+            }
+
+            unsafe fn fetch_unchecked<'w>(
+                fetch: &Self::Fetch<'w>,
+                row: usize,
+                entity: Entity,
+            ) -> Result<Self::Item<'w>, EcsError> {
+                let ($($name,)*) = fetch;
+                // SAFETY: forwarded from the caller, terms conflicting with each other fail borrow_columns
+                Ok(($(unsafe { $name::fetch_unchecked($name, row, entity)? },)*))
+            }
+        }
+
+        impl<$($name: ReadOnlyQueryData),*> ReadOnlyQueryData for ($($name,)*) {}
+
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {}
+
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($name: QueryFilter),*> WorldQuery for Or<($($name,)*)> {
+            type State = ($($name::State,)*);
+            type Fetch<'w> = ($(Option<$name::Fetch<'w>>,)*);
 
-type Component = Rc<RefCell<dyn Any + 'static>>;
+            fn init_state(world: &World) -> Self::State {
+                ($($name::init_state(world),)*)
+            }
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
 
-/**
-Struct made for querying entities with specyfic components. It is similar to builder pattern things but build function is either [Query::run()] or [Query::run_entity()].
- */
-pub struct Query<'a> {
-    map: u128,
+            fn init_fetch<'w>(
+                state: &Self::State,
+                world: &'w World,
+                archetype: &'w Archetype,
+                ticks: SystemTicks,
+            ) -> Option<Self::Fetch<'w>> {
+                let ($($name,)*) = state;
+                let fetch = ($($name::init_fetch($name, world, archetype, ticks),)*);
+                let ($($name,)*) = &fetch;
+                (false $(|| $name.is_some())*).then_some(fetch)
+            }
 
-    components_bit_masks: &'a HashMap<TypeId, u128>,
-    type_ids: Vec<TypeId>,
-    entities_bit_maps: &'a Vec<u128>,
-    components: &'a HashMap<TypeId, Vec<Option<Component>>>,
+            fn matches(fetch: &Self::Fetch<'_>, row: usize, entity: Entity) -> bool {
+                let ($($name,)*) = fetch;
+                false $(|| $name.as_ref().is_some_and(|fetch| $name::matches(fetch, row, entity)))*
+            }
+        }
+
+        impl<$($name: QueryFilter),*> QueryFilter for Or<($($name,)*)> {}
+    };
 }
 
-impl<'a> Query<'a> {
-    pub fn new(
-        components_bit_masks: &'a HashMap<TypeId, u128>,
-        entities_bit_maps: &'a Vec<u128>,
-        components: &'a HashMap<TypeId, Vec<Option<Component>>>,
-    ) -> Self {
+impl_world_query_tuple!();
+impl_world_query_tuple!(A);
+impl_world_query_tuple!(A, B);
+impl_world_query_tuple!(A, B, C);
+impl_world_query_tuple!(A, B, C, D);
+impl_world_query_tuple!(A, B, C, D, E);
+impl_world_query_tuple!(A, B, C, D, E, F);
+impl_world_query_tuple!(A, B, C, D, E, F, G);
+impl_world_query_tuple!(A, B, C, D, E, F, G, H);
+impl_world_query_tuple!(A, B, C, D, E, F, G, H, I);
+impl_world_query_tuple!(A, B, C, D, E, F, G, H, I, J);
+
+/// Statically typed query created with [World::query()]. Components are returned already downcasted.
+///
+/// Example:
+/// ```
+/// use wgtr_ecs::*;
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Speed(u32);
+///
+/// let mut world = World::new();
+/// world.register_component::<Health>();
+/// world.register_component::<Speed>();
+/// world.create_entity()
+///     .with_component(Health(100)).unwrap()
+///     .with_component(Speed(10)).unwrap();
+///
+/// for (health, mut speed) in world.query::<(&Health, &mut Speed)>().iter_mut() {
+///     speed.0 += health.0;
+/// }
+/// world.query::<&mut Speed>().for_each_mut(|mut speed| speed.0 *= 2);
+/// let speeds: Vec<u32> = world.query::<&Speed>().iter().map(|speed| speed.0).collect();
+/// assert_eq!(speeds, [220]);
+/// ```
+///
+/// The second type parameter filters entities without fetching anything, see [World::query_filtered()].
+pub struct Query<'w, D: QueryData, F: QueryFilter = ()> {
+    world: &'w World,
+    state: D::State,
+    filter_state: F::State,
+    ticks: SystemTicks,
+}
+
+impl<'w, D: QueryData, F: QueryFilter> Query<'w, D, F> {
+    pub(crate) fn new(world: &'w World, ticks: SystemTicks) -> Self {
         Self {
-            map: 0,
-            components_bit_masks,
-            components,
-            type_ids: vec![],
-            entities_bit_maps,
-        }
-    }
-
-    pub fn with_component<T: Any>(&mut self) -> Result<&mut Self, &'static str> {
-        let type_id = TypeId::of::<T>();
-        let component_mask = self
-            .components_bit_masks
-            .get(&type_id)
-            .ok_or("Tried to query component that was not registered")?;
-        self.map |= *component_mask;
-        self.type_ids.push(type_id);
-        Ok(self)
-    }
-
-    pub fn run(&self) -> (Vec<usize>, Vec<Vec<Component>>) {
-        let indexes: Vec<usize> = self
-            .entities_bit_maps
-            .iter()
-            .enumerate()
-            .filter_map(|(index, entity_map)| {
-                if entity_map & self.map == self.map {
-                    return Some(index);
+            world,
+            state: D::init_state(world),
+            filter_state: F::init_state(world),
+            ticks,
+        }
+    }
+
+    /// Lazily fetches the data of every matching entity, archetype after archetype, without collecting them first.
+    /// Only available for queries which do not borrow components mutably, use [Query::iter_mut()] for those.
+    ///
+    /// Panics like [Query::run()] if a fetched component is already borrowed in a conflicting way, see [Query::try_iter()].
+    pub fn iter(&self) -> QueryIter<'w, '_, D, F>
+    where
+        D: ReadOnlyQueryData,
+    {
+        QueryIter::new(self.world, &self.state, &self.filter_state, self.ticks)
+    }
+
+    /// Same as [Query::iter()] for any query, the items can not outlive the mutable borrow of the query.
+    pub fn iter_mut(&mut self) -> QueryIter<'_, '_, D, F> {
+        QueryIter::new(self.world, &self.state, &self.filter_state, self.ticks)
+    }
+
+    /// Calls the closure for every matching entity. Borrows the columns of each archetype once and loops over its
+    /// rows directly, so it is usually faster than iterating. Items can not outlive the call of the closure.
+    ///
+    /// Panics like [Query::run()] if a fetched component is already borrowed in a conflicting way,
+    /// see [Query::try_for_each()].
+    pub fn for_each(&self, f: impl FnMut(D::Item<'_>))
+    where
+        D: ReadOnlyQueryData,
+    {
+        self.try_for_each(f).unwrap_or_else(|error| panic!("{error}"));
+    }
+
+    /// Same as [Query::for_each()] for any query.
+    pub fn for_each_mut(&mut self, f: impl FnMut(D::Item<'_>)) {
+        self.try_for_each_mut(f).unwrap_or_else(|error| panic!("{error}"));
+    }
+
+    /// Same as [Query::iter()] but returns [EcsError::AlreadyBorrowed] for items whose components are already borrowed
+    /// in a conflicting way instead of panicking.
+    pub fn try_iter(&self) -> TryQueryIter<'w, '_, D, F>
+    where
+        D: ReadOnlyQueryData,
+    {
+        TryQueryIter(self.iter())
+    }
+
+    /// Same as [Query::iter_mut()] but returns errors instead of panicking, see [Query::try_iter()].
+    pub fn try_iter_mut(&mut self) -> TryQueryIter<'_, '_, D, F> {
+        TryQueryIter(self.iter_mut())
+    }
+
+    /// Same as [Query::for_each()] but stops at the first item whose components are already borrowed
+    /// in a conflicting way and returns [EcsError::AlreadyBorrowed].
+    pub fn try_for_each(&self, f: impl FnMut(D::Item<'_>)) -> Result<(), EcsError>
+    where
+        D: ReadOnlyQueryData,
+    {
+        self.fold_archetypes(f)
+    }
+
+    /// Same as [Query::for_each_mut()] but returns errors instead of panicking, see [Query::try_for_each()].
+    pub fn try_for_each_mut(&mut self, f: impl FnMut(D::Item<'_>)) -> Result<(), EcsError> {
+        self.fold_archetypes(f)
+    }
+
+    /// Fetches the data of every matching entity, archetype after archetype.
+    ///
+    /// Panics if a fetched component is already borrowed in a conflicting way, for example by an item of another query
+    /// which is still alive, see [Query::try_run()].
+    pub fn run(&self) -> Vec<D::Item<'w>> {
+        self.try_run().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Fetches the data of every matching entity, returns [EcsError::AlreadyBorrowed] if a fetched component
+    /// is already borrowed in a conflicting way.
+    pub fn try_run(&self) -> Result<Vec<D::Item<'w>>, EcsError> {
+        let mut result = vec![];
+        for archetype in self.world.archetypes.iter() {
+            let Some((fetch, filter)) = self.init_fetch(archetype) else {
+                continue;
+            };
+            for (row, entity) in archetype.entities().iter().enumerate() {
+                if D::matches(&fetch, row, *entity) && F::matches(&filter, row, *entity) {
+                    result.push(D::fetch(&fetch, row, *entity)?);
                 }
-                None
+            }
+        }
+        Ok(result)
+    }
+
+    /// Fetches the data of a single entity. Returns an error if the entity is not alive, does not match the query
+    /// or its components are already borrowed in a conflicting way.
+    pub fn get(&self, entity: Entity) -> Result<D::Item<'w>, EcsError> {
+        let location = self
+            .world
+            .entities
+            .location(entity)
+            .ok_or(EcsError::NoSuchEntity(entity))?;
+        let archetype = self.world.archetypes.get(location.archetype);
+        self.init_fetch(archetype)
+            .filter(|(fetch, filter)| {
+                D::matches(fetch, location.row, entity) && F::matches(filter, location.row, entity)
             })
-            .collect();
-        let mut result = vec![];
+            .ok_or(EcsError::QueryMismatch(entity))
+            .and_then(|(fetch, _)| D::fetch(&fetch, location.row, entity))
+    }
 
-        for type_id in &self.type_ids {
-            let entity_components = self.components.get(type_id).unwrap();
-            let mut components_to_keep = vec![];
-            for index in &indexes {
-                components_to_keep.push(entity_components[*index].as_ref().unwrap().clone());
+    /// Borrows the columns of every archetype once and calls the closure for its matching rows. If a column is
+    /// already borrowed, for example because a single component of it is, the rows of the archetype are borrowed
+    /// one by one instead, which only fails for the components which actually conflict.
+    fn fold_archetypes(&self, mut f: impl FnMut(D::Item<'_>)) -> Result<(), EcsError> {
+        let mut borrows = vec![];
+        for archetype in self.world.archetypes.iter() {
+            let Some((fetch, filter)) = self.init_fetch(archetype) else {
+                continue;
+            };
+            let borrowed = D::borrow_columns(&fetch, &mut borrows);
+            if !borrowed {
+                borrows.clear();
+            }
+            for (row, entity) in archetype.entities().iter().enumerate() {
+                if !(D::matches(&fetch, row, *entity) && F::matches(&filter, row, *entity)) {
+                    continue;
+                }
+                if borrowed {
+                    // SAFETY: `borrows` lives until the end of the archetype while the closure can not keep the item
+                    f(unsafe { D::fetch_unchecked(&fetch, row, *entity)? });
+                } else {
+                    f(D::fetch(&fetch, row, *entity)?);
+                }
             }
-            result.push(components_to_keep);
+            borrows.clear();
         }
+        Ok(())
+    }
 
-        (indexes, result)
+    fn init_fetch(&self, archetype: &'w Archetype) -> Option<(D::Fetch<'w>, F::Fetch<'w>)> {
+        let fetch = D::init_fetch(&self.state, self.world, archetype, self.ticks)?;
+        let filter = F::init_fetch(&self.filter_state, self.world, archetype, self.ticks)?;
+        Some((fetch, filter))
     }
+}
 
-    pub fn run_entity(&self) -> Vec<QueryEntity> {
-        self.entities_bit_maps
-            .iter()
-            .enumerate()
-            .filter_map(|(index, entity_map)| {
-                if entity_map & self.map == self.map {
-                    Some(QueryEntity::new(index, self.components))
-                } else {
-                    None
+/// Lazy iterator over the items of a [Query], created with [Query::iter()] and [Query::iter_mut()].
+///
+/// Items can outlive the iterator, so every fetched component is borrowed on its own with an atomic flag.
+/// [Query::for_each()] borrows whole columns instead and is faster when the items are only needed in a closure.
+///
+/// Panics like [Query::run()] if a fetched component is already borrowed in a conflicting way, see [TryQueryIter].
+pub struct QueryIter<'w, 's, D: QueryData, F: QueryFilter> {
+    world: &'w World,
+    state: &'s D::State,
+    filter_state: &'s F::State,
+    ticks: SystemTicks,
+    archetypes: slice::Iter<'w, Archetype>,
+    fetch: Option<(D::Fetch<'w>, F::Fetch<'w>)>, // None if the current archetype can not match
+    entities: &'w [Entity],
+    row: usize,
+    remaining: usize, // rows left to check, the upper bound of the size hint
+}
+
+impl<'w, 's, D: QueryData, F: QueryFilter> QueryIter<'w, 's, D, F> {
+    fn new(world: &'w World, state: &'s D::State, filter_state: &'s F::State, ticks: SystemTicks) -> Self {
+        Self {
+            world,
+            state,
+            filter_state,
+            ticks,
+            archetypes: world.archetypes.iter(),
+            fetch: None,
+            entities: &[],
+            row: 0,
+            remaining: world.archetypes.iter().map(Archetype::len).sum(),
+        }
+    }
+
+    fn init_fetch(&self, archetype: &'w Archetype) -> Option<(D::Fetch<'w>, F::Fetch<'w>)> {
+        let fetch = D::init_fetch(self.state, self.world, archetype, self.ticks)?;
+        let filter = F::init_fetch(self.filter_state, self.world, archetype, self.ticks)?;
+        Some((fetch, filter))
+    }
+
+    fn try_next(&mut self) -> Option<Result<D::Item<'w>, EcsError>> {
+        loop {
+            if let Some((fetch, filter)) = &self.fetch {
+                while let Some(entity) = self.entities.get(self.row) {
+                    let row = self.row;
+                    self.row += 1;
+                    self.remaining -= 1;
+                    if D::matches(fetch, row, *entity) && F::matches(filter, row, *entity) {
+                        return Some(D::fetch(fetch, row, *entity));
+                    }
                 }
-            })
-            .collect()
+            }
+            let archetype = self.archetypes.next()?;
+            self.fetch = self.init_fetch(archetype);
+            self.row = 0;
+            self.entities = match self.fetch {
+                Some(_) => archetype.entities(),
+                None => {
+                    self.remaining -= archetype.len();
+                    &[]
+                }
+            };
+        }
+    }
+
+    /// Loops over the rows of every archetype left without going through [QueryIter::try_next()].
+    fn fold_results<B>(mut self, init: B, mut f: impl FnMut(B, Result<D::Item<'w>, EcsError>) -> B) -> B {
+        let mut accumulator = init;
+        if let Some(fetch) = &self.fetch {
+            accumulator = Self::fold_rows(fetch, self.entities, self.row, accumulator, &mut f);
+        }
+        let archetypes = std::mem::replace(&mut self.archetypes, [].iter());
+        for archetype in archetypes {
+            if let Some(fetch) = self.init_fetch(archetype) {
+                accumulator = Self::fold_rows(&fetch, archetype.entities(), 0, accumulator, &mut f);
+            }
+        }
+        accumulator
+    }
+
+    /// Folds the matching rows of one archetype starting at `start`.
+    fn fold_rows<B>(
+        (fetch, filter): &(D::Fetch<'w>, F::Fetch<'w>),
+        entities: &[Entity],
+        start: usize,
+        mut accumulator: B,
+        f: &mut impl FnMut(B, Result<D::Item<'w>, EcsError>) -> B,
+    ) -> B {
+        for (row, entity) in entities.iter().enumerate().skip(start) {
+            if D::matches(fetch, row, *entity) && F::matches(filter, row, *entity) {
+                accumulator = f(accumulator, D::fetch(fetch, row, *entity));
+            }
+        }
+        accumulator
+    }
+}
+
+impl<'w, D: QueryData, F: QueryFilter> Iterator for QueryIter<'w, '_, D, F> {
+    type Item = D::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().map(unwrap_item)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+
+    /// Used by [Iterator::for_each()] and most other consuming adapters, loops over the rows of every archetype
+    /// without going through [Iterator::next()].
+    fn fold<B, G: FnMut(B, Self::Item) -> B>(self, init: B, mut f: G) -> B {
+        self.fold_results(init, |accumulator, item| f(accumulator, unwrap_item(item)))
+    }
+}
+
+/// Iterator like [QueryIter] which returns [EcsError::AlreadyBorrowed] for items whose components are already borrowed
+/// in a conflicting way instead of panicking, created with [Query::try_iter()] and [Query::try_iter_mut()].
+pub struct TryQueryIter<'w, 's, D: QueryData, F: QueryFilter>(QueryIter<'w, 's, D, F>);
+
+impl<'w, D: QueryData, F: QueryFilter> Iterator for TryQueryIter<'w, '_, D, F> {
+    type Item = Result<D::Item<'w>, EcsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.try_next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+
+    fn fold<B, G: FnMut(B, Self::Item) -> B>(self, init: B, f: G) -> B {
+        self.0.fold_results(init, f)
+    }
+}
+
+fn unwrap_item<T>(item: Result<T, EcsError>) -> T {
+    item.unwrap_or_else(|error| panic!("{error}"))
 }
 
 #[cfg(test)]
 mod test {
-    use std::{any::TypeId, cell::{Ref, RefMut}};
+    use crate::{AtomicRef, AtomicRefMut, Component, EcsError, Entity, StorageType, World};
 
-    use crate::{{World, QueryEntity}, make_query};
+    #[derive(Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Speed(f32);
 
     #[test]
-    fn query_mask_updating_with_component() -> Result<(), &'static str> {
+    fn typed_query_fetches_tuples() -> Result<(), EcsError> {
         let mut world = World::new();
-        world.register_component::<u32>();
-        world.register_component::<f32>();
-        let mut query = world.query();
-        query.with_component::<u32>()?.with_component::<f32>()?;
-
-        assert_eq!(query.map, 3);
-        assert_eq!(TypeId::of::<u32>(), query.type_ids[0]);
-        assert_eq!(TypeId::of::<f32>(), query.type_ids[1]);
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
 
+        world.create_entity().with_component(Health(5))?;
+        let entity = world
+            .create_entity()
+            .with_component(Health(420))?
+            .with_component(Speed(11.5))?
+            .id();
+        world.create_entity().with_component(Speed(0.0))?;
+
+        let result: Vec<(Entity, AtomicRef<Health>, AtomicRefMut<Speed>)> =
+            world.query::<(Entity, &Health, &mut Speed)>().run();
+        assert_eq!(result.len(), 1);
+        let (id, health, mut speed) = result.into_iter().next().unwrap();
+        assert_eq!(id, entity);
+        assert_eq!(health.0, 420);
+        speed.0 += 1.0;
+        drop(speed);
+
+        assert_eq!(world.query::<&Speed>().get(entity)?.0, 12.5);
+        assert_eq!(world.query::<&Health>().run().len(), 2);
         Ok(())
     }
+
     #[test]
-    fn macro_query_mask_updating_with_component() -> Result<(), &'static str>{
+    fn typed_query_mixes_storages() -> Result<(), EcsError> {
         let mut world = World::new();
-        world.register_component::<u32>();
-        world.register_component::<f32>();
-        let mut query = world.query();
-        make_query!(query, u32, f32);
-
-        assert_eq!(query.map, 3);
-        assert_eq!(TypeId::of::<u32>(), query.type_ids[0]);
-        assert_eq!(TypeId::of::<f32>(), query.type_ids[1]);
+        world.register_component::<Health>();
+        world.register_component_with_storage::<Speed>(StorageType::SparseSet)?;
 
+        world.create_entity().with_component(Health(1))?;
+        let entity = world
+            .create_entity()
+            .with_component(Health(2))?
+            .with_component(Speed(2.0))?
+            .id();
+
+        let result = world.query::<(Entity, &Health, &Speed)>().run();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, entity);
+        assert_eq!(result[0].2 .0, 2.0);
         Ok(())
     }
+
     #[test]
-    fn run_query() -> Result<(), &'static str> {
+    fn typed_query_with_unregistered_component_is_empty() -> Result<(), EcsError> {
         let mut world = World::new();
-        world.register_component::<u32>();
-        world.register_component::<f32>();
-
-        world.create_entity().with_component(5_u32)?;
-        world
-            .create_entity()
-            .with_component(420_u32)?
-            .with_component(11.1_f32)?;
-        world.create_entity().with_component(0.0_f32)?;
-
-        let mut query = world.query();
-        query.with_component::<u32>()?.with_component::<f32>()?;
-        let query_result = query.run();
-
-        let u32s = &query_result.1[0];
-        let f32s = &query_result.1[1];
-        let indexes = &query_result.0;
-        assert_eq!(u32s.len(), 1);
-        assert_eq!(f32s.len(), 1);
-        assert_eq!(u32s.len(), indexes.len());
-        assert_eq!(indexes[0], 1);
-
-        let first_u32 = u32s[0].borrow();
-        let extracted_u32 = first_u32.downcast_ref::<u32>().unwrap();
-        assert_eq!(*extracted_u32, 420);
+        world.register_component::<Health>();
+        let entity = world.create_entity().with_component(Health(1))?.id();
 
+        assert!(world.query::<(&Health, &Speed)>().run().is_empty());
+        assert!(world.query::<&Speed>().get(entity).is_err());
         Ok(())
     }
+
     #[test]
-    fn query_for_entity_ref() -> Result<(), &'static str> {
+    fn query_iter_resumes_where_next_stopped() -> Result<(), EcsError> {
         let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_component::<Speed>();
 
-        world.register_component::<u32>();
-        world.register_component::<f32>();
-        world.create_entity().with_component(100_u32)?;
-        world.create_entity().with_component(10.0_f32)?;
-
-        let mut query = world.query();
-        let entities: Vec<QueryEntity> = query.with_component::<u32>()?.run_entity();
-
-        assert_eq!(entities.len(), 1);
-
-        for entity in entities {
-            assert_eq!(entity.id, 0);
-            let health: Ref<u32> = entity.get_component::<u32>()?;
-            assert_eq!(*health, 100);
+        for health in 0..3 {
+            world.create_entity().with_component(Health(health))?;
         }
+        world.create_entity().with_component(Speed(0.0))?;
+        for health in 3..6 {
+            let entity = world.create_entity().with_component(Health(health))?.id();
+            if health % 2 == 0 {
+                world.add_component(Speed(0.0), entity)?;
+            }
+        }
+
+        let query = world.query::<&Health>();
+        let mut iter = query.iter();
+        assert_eq!(iter.size_hint(), (0, Some(7)));
+        assert_eq!(iter.next().map(|health| health.0), Some(0));
+        assert_eq!(iter.size_hint(), (0, Some(6)));
+        let rest = iter.fold(vec![], |mut rest, health| {
+            rest.push(health.0);
+            rest
+        });
+        assert_eq!(rest, [1, 2, 3, 5, 4]);
+
+        let mut with_speed = vec![];
+        world
+            .query::<(&Health, &Speed)>()
+            .for_each(|(health, _)| with_speed.push(health.0));
+        assert_eq!(with_speed, [4]);
         Ok(())
     }
 
     #[test]
-    fn query_for_entity_mut() -> Result<(), &'static str> {
+    fn components_are_borrowed_one_by_one() -> Result<(), EcsError> {
         let mut world = World::new();
+        world.register_component_with_storage::<Speed>(StorageType::SparseSet)?;
+        let slow = world.spawn(Speed(1.0));
+        let fast = world.spawn((Speed(2.0), Health(1)));
+
+        let mut query = world.query::<&mut Speed>();
+        let mut iter = query.iter_mut();
+        let mut first = iter.next().unwrap();
+        first.0 *= 2.0;
+        // entities sharing the sparse set column are still borrowed on their own
+        assert!(world.get::<Speed>(slow).is_err());
+        assert!(world.entity(fast)?.get_mut::<Speed>().is_ok());
+        let mut second = iter.next().unwrap();
+        second.0 += first.0;
+        drop((first, second));
+        assert_eq!(world.get::<Speed>(fast)?.0, 4.0);
+
+        // two terms borrowing the same component still conflict
+        assert!(world.query::<(&mut Speed, &mut Speed)>().try_run().is_err());
+        assert!(world.query::<(&mut Speed, Option<&mut Speed>)>().get(fast).is_err());
+        Ok(())
+    }
 
-        world.register_component::<u32>();
-        world.register_component::<f32>();
-        world.create_entity().with_component(100_u32)?;
-        world.create_entity().with_component(10.0_f32)?;
-
-        let mut query = world.query();
-        let entities: Vec<QueryEntity> = query.with_component::<u32>()?.run_entity();
-
-        assert_eq!(entities.len(), 1);
-
-        for entity in entities {
-            assert_eq!(entity.id, 0);
-            let mut health: RefMut<u32> = entity.get_component_mut::<u32>()?;
-            assert_eq!(*health, 100);
-            *health += 1;
-        }
-
-        let entities: Vec<QueryEntity> = query.with_component::<u32>()?.run_entity();
-        for entity in entities {
-            let health: Ref<u32> = entity.get_component::<u32>()?;
-            assert_eq!(*health, 101);
-        }
+    #[test]
+    fn for_each_borrows_whole_columns() -> Result<(), EcsError> {
+        let mut world = World::new();
+        world.register_component_with_storage::<Speed>(StorageType::SparseSet)?;
+        let slow = world.spawn(Speed(1.0));
+        let fast = world.spawn((Speed(2.0), Health(1)));
+
+        let mut query = world.query::<&mut Speed>();
+        query.for_each_mut(|mut speed| speed.0 *= 2.0);
+        assert!(world
+            .query::<&Speed>()
+            .try_for_each(|_| assert!(world.get::<Speed>(slow).is_ok()))
+            .is_ok());
+        let mut visited = 0;
+        query.for_each_mut(|_| {
+            visited += 1;
+            assert!(world.get::<Speed>(fast).is_err());
+        });
+        assert_eq!(visited, 2);
+
+        // a single borrowed component only stops the rows which actually need it
+        let slow_speed = world.get::<Speed>(slow)?;
+        let mut speeds = vec![];
+        world.query::<&Speed>().for_each(|speed| speeds.push(speed.0));
+        assert_eq!(speeds, [2.0, 4.0]);
+        assert!(world.query::<&mut Speed>().try_for_each_mut(|_| {}).is_err());
+        world
+            .query::<(&mut Speed, &Health)>()
+            .for_each_mut(|(mut speed, _)| speed.0 += 1.0);
+        drop(slow_speed);
+        assert_eq!(world.get::<Speed>(fast)?.0, 5.0);
+
+        // two terms borrowing the same component still conflict
+        assert!(world
+            .query::<(&mut Speed, &mut Speed)>()
+            .try_for_each_mut(|_| {})
+            .is_err());
+        assert!(world
+            .query::<(&Speed, Option<&mut Speed>)>()
+            .try_for_each_mut(|_| {})
+            .is_err());
+        assert!(world.query::<(&mut Speed, Option<&mut Speed>)>().get(fast).is_err());
         Ok(())
     }
 }
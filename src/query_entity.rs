@@ -1,73 +1,56 @@
-use std::{
-    any::{Any, TypeId},
-    cell::{Ref, RefCell, RefMut},
-    rc::Rc, collections::HashMap,
-};
+use crate::{archetype::EntityLocation, AtomicRef, AtomicRefMut, Component, EcsError, Entity, EntityRef, World};
 
-type Component = Rc<RefCell<dyn Any + 'static>>;
-
-type ExtractedComponents<'a> = Result<&'a Vec<Option<Rc<RefCell<dyn Any>>>>, &'static str>;
-
-/// Helper struct made for iterating over entities with [crate::Query::run_entity()].
-/// 
+/// Helper struct made for iterating over entities with [crate::DynamicQuery::run_entity()].
+///
 /// Example:
 /// ```
 /// use wgtr_ecs::*; // for macros
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Speed(f32);
+///
 /// let mut world = World::new();
 ///
-/// world.register_component::<u32>();
-/// world.register_component::<f32>();
+/// world.register_component::<Health>();
+/// world.register_component::<Speed>();
 ///
 /// world.create_entity()
-///     .with_component(10u32).unwrap()
-///     .with_component(10.1f32).unwrap();
+///     .with_component(Health(10)).unwrap()
+///     .with_component(Speed(10.1)).unwrap();
+///
+/// let mut query = world.dynamic_query();
+/// make_query!(query, Health, Speed);
 ///
-/// let mut query = world.query();
-/// make_query!(query, u32, f32);
-/// 
 /// for entity in query.run_entity(){ // entity is crate::QueryEntity
-///     let mut f = get_component!(entity, &mut f32);
-///     *f += 1.0;
+///     let mut speed = get_component!(entity, &mut Speed);
+///     speed.0 += 1.0;
 /// }
 ///
 /// ```
 pub struct QueryEntity<'a> {
-    pub id: usize,
-    components: &'a HashMap<TypeId, Vec<Option<Component>>>,
+    pub id: Entity,
+    location: EntityLocation,
+    world: &'a World,
 }
 
 impl<'a> QueryEntity<'a> {
-    pub fn new(id: usize, components: &'a HashMap<TypeId, Vec<Option<Component>>>) -> Self {
-        Self { id, components }
+    pub(crate) fn new(id: Entity, location: EntityLocation, world: &'a World) -> Self {
+        Self { id, location, world }
     }
 
-    fn extract_components<T: Any>(&self) -> ExtractedComponents {
-        let type_id = TypeId::of::<T>();
-        self
-            .components
-            .get(&type_id)
-            .ok_or_else(|| "Attempting to use not registered component")
+    fn entity_ref(&self) -> EntityRef<'a> {
+        EntityRef::new(self.world, self.id, self.location)
     }
 
-    pub fn get_component<T: Any>(&self) -> Result<Ref<T>, &'static str> {
-        let components = self.extract_components::<T>()?;
-        let borrowed_component = components[self.id]
-            .as_ref()
-            .ok_or_else(||"Attempting to get component from entity that does not have one")?
-            .borrow();
-        Ok(Ref::map(borrowed_component, |any| {
-            any.downcast_ref::<T>().unwrap()
-        }))
+    /// Returns an error if the component is borrowed mutably.
+    pub fn get_component<T: Component>(&self) -> Result<AtomicRef<'a, T>, EcsError> {
+        self.entity_ref().get()
     }
 
-    pub fn get_component_mut<T: Any>(&self) -> Result<RefMut<T>, &'static str> {
-        let components = self.extract_components::<T>()?;
-        let borrowed_component = components[self.id]
-            .as_ref()
-            .ok_or_else(||"Attempting to get component from entity that does not have one")?
-            .borrow_mut();
-        Ok(RefMut::map(borrowed_component, |any| {
-            any.downcast_mut::<T>().unwrap()
-        }))
+    /// Borrows the component mutably and marks it as changed, see [crate::Changed].
+    /// Returns an error if the component is borrowed.
+    pub fn get_component_mut<T: Component>(&self) -> Result<AtomicRefMut<'a, T>, EcsError> {
+        self.entity_ref().get_mut()
     }
 }
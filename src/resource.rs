@@ -0,0 +1,169 @@
+use std::{
+    any::{type_name, Any},
+    cell::{Ref, RefCell, RefMut},
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    thread::{self, ThreadId},
+};
+
+use crate::{AtomicRef, AtomicRefMut};
+
+/// Shared borrow of a resource of type `T`, returned by [crate::World::get_resource()] and usable as a function system parameter.
+pub struct Res<'w, T> {
+    value: AtomicRef<'w, Box<dyn Any + Send + Sync>>,
+    _marker: PhantomData<&'w T>,
+}
+
+impl<'w, T: Any> Res<'w, T> {
+    /// Returns None if the resource is not of type `T`.
+    pub(crate) fn new(value: AtomicRef<'w, Box<dyn Any + Send + Sync>>) -> Option<Self> {
+        value.is::<T>().then_some(Self {
+            value,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Any> Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.downcast_ref().expect("type is checked when borrowing")
+    }
+}
+
+/// Exclusive borrow of a resource of type `T`, returned by [crate::World::get_resource_mut()] and usable as a function system parameter.
+pub struct ResMut<'w, T> {
+    value: AtomicRefMut<'w, Box<dyn Any + Send + Sync>>,
+    _marker: PhantomData<&'w mut T>,
+}
+
+impl<'w, T: Any> ResMut<'w, T> {
+    /// Returns None if the resource is not of type `T`.
+    pub(crate) fn new(value: AtomicRefMut<'w, Box<dyn Any + Send + Sync>>) -> Option<Self> {
+        value.is::<T>().then_some(Self {
+            value,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Any> Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.downcast_ref().expect("type is checked when borrowing")
+    }
+}
+
+impl<T: Any> DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.downcast_mut().expect("type is checked when borrowing")
+    }
+}
+
+/// Resource which is not `Send`, added with [crate::World::add_non_send_resource()]. Only the thread which added it can use it,
+/// so the [crate::World] stays `Send + Sync`. Dropping it on another thread leaks the value instead.
+pub(crate) struct NonSendCell {
+    thread: ThreadId,
+    value: ManuallyDrop<RefCell<Box<dyn Any>>>,
+}
+
+// SAFETY: the value is only touched by the thread which created it, every access checks the thread first
+// and the value is leaked when dropped anywhere else
+unsafe impl Send for NonSendCell {}
+unsafe impl Sync for NonSendCell {}
+
+impl NonSendCell {
+    pub(crate) fn new(value: Box<dyn Any>) -> Self {
+        Self {
+            thread: thread::current().id(),
+            value: ManuallyDrop::new(RefCell::new(value)),
+        }
+    }
+
+    /// Panics if called from another thread than the one which added the resource.
+    pub(crate) fn get<T>(&self) -> &RefCell<Box<dyn Any>> {
+        self.check_thread::<T>();
+        &self.value
+    }
+
+    /// Panics if called from another thread than the one which added the resource.
+    pub(crate) fn get_mut<T>(&mut self) -> &mut Box<dyn Any> {
+        self.check_thread::<T>();
+        self.value.get_mut()
+    }
+
+    fn check_thread<T>(&self) {
+        assert!(
+            thread::current().id() == self.thread,
+            "Non-Send resource {} can only be used on the thread it was added on",
+            type_name::<T>()
+        );
+    }
+}
+
+impl Drop for NonSendCell {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // SAFETY: the value is never used again
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
+
+/// Shared borrow of a non-Send resource, see [crate::World::add_non_send_resource()]. Usable as a function system parameter,
+/// systems using it run on the thread calling [crate::Systems::update()].
+pub struct NonSend<'w, T> {
+    value: Ref<'w, Box<dyn Any>>,
+    _marker: PhantomData<&'w T>,
+}
+
+impl<'w, T: Any> NonSend<'w, T> {
+    /// Returns None if the resource is not of type `T`.
+    pub(crate) fn new(value: Ref<'w, Box<dyn Any>>) -> Option<Self> {
+        value.is::<T>().then_some(Self {
+            value,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Any> Deref for NonSend<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.downcast_ref().expect("type is checked when borrowing")
+    }
+}
+
+/// Exclusive borrow of a non-Send resource, see [NonSend].
+pub struct NonSendMut<'w, T> {
+    value: RefMut<'w, Box<dyn Any>>,
+    _marker: PhantomData<&'w mut T>,
+}
+
+impl<'w, T: Any> NonSendMut<'w, T> {
+    /// Returns None if the resource is not of type `T`.
+    pub(crate) fn new(value: RefMut<'w, Box<dyn Any>>) -> Option<Self> {
+        value.is::<T>().then_some(Self {
+            value,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Any> Deref for NonSendMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.downcast_ref().expect("type is checked when borrowing")
+    }
+}
+
+impl<T: Any> DerefMut for NonSendMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.downcast_mut().expect("type is checked when borrowing")
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    error::Error,
+    fmt::{self, Display},
+};
+
+use crate::{IntoSystem, System};
+
+/// Stage of [crate::Systems]. Stages run one after another and [crate::Commands] are applied at the end of each.
+///
+/// [crate::Systems::update()] runs every stage before [Stage::Render], [crate::Systems::render()] runs the rest.
+/// User defined stages are added with [crate::Systems::add_stage_before()] and [crate::Systems::add_stage_after()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreUpdate,
+    Update,
+    PostUpdate,
+    Render,
+    Custom(&'static str),
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Custom(name) => write!(f, "{name}"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// System together with its stage, labels and ordering constraints, created by the methods of [IntoSystemConfig].
+pub struct SystemConfig<'a> {
+    pub(crate) system: Box<dyn 'a + System>,
+    pub(crate) stage: Stage,
+    labels: Vec<&'static str>,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>,
+}
+
+impl SystemConfig<'_> {
+    /// Label if the system has one, otherwise [System::name()].
+    pub(crate) fn name(&self) -> String {
+        match self.labels.first() {
+            Some(label) => label.to_string(),
+            None => self.system.name(),
+        }
+    }
+
+    /// Returns true if a `before` or `after` constraint of either system names a label of the other one.
+    pub(crate) fn is_ordered_with(&self, other: &SystemConfig) -> bool {
+        let names = |constraints: &[&'static str], config: &SystemConfig| {
+            constraints.iter().any(|label| config.labels.contains(label))
+        };
+        names(&self.before, other) || names(&self.after, other) || names(&other.before, self) || names(&other.after, self)
+    }
+}
+
+#[doc(hidden)]
+pub struct IsSystemConfig;
+
+/**
+Configuration of a system added to [crate::Systems], implemented for everything that can be turned into a system.
+
+Example:
+```
+use wgtr_ecs::*;
+fn input() {}
+fn movement() {}
+fn collisions() {}
+
+let mut systems = Systems::new();
+systems
+    .with_system(collisions.after("movement"))
+    .with_system(movement.label("movement").after("input"))
+    .with_system(input.label("input").in_stage(Stage::PreUpdate));
+```
+ */
+pub trait IntoSystemConfig<'a, Marker>: Sized {
+    fn into_config(self) -> SystemConfig<'a>;
+
+    /// Names the system so other systems can be ordered relative to it. Many systems can share a label.
+    fn label(self, label: &'static str) -> SystemConfig<'a> {
+        let mut config = self.into_config();
+        config.labels.push(label);
+        config
+    }
+
+    /// Runs the system before all systems of its stage with the label.
+    fn before(self, label: &'static str) -> SystemConfig<'a> {
+        let mut config = self.into_config();
+        config.before.push(label);
+        config
+    }
+
+    /// Runs the system after all systems of its stage with the label.
+    fn after(self, label: &'static str) -> SystemConfig<'a> {
+        let mut config = self.into_config();
+        config.after.push(label);
+        config
+    }
+
+    /// Puts the system into the stage instead of [Stage::Update].
+    fn in_stage(self, stage: Stage) -> SystemConfig<'a> {
+        let mut config = self.into_config();
+        config.stage = stage;
+        config
+    }
+}
+
+impl<'a> IntoSystemConfig<'a, IsSystemConfig> for SystemConfig<'a> {
+    fn into_config(self) -> SystemConfig<'a> {
+        self
+    }
+}
+
+impl<'a, M, S: IntoSystem<M>> IntoSystemConfig<'a, M> for S
+where
+    S::System: 'a,
+{
+    fn into_config(self) -> SystemConfig<'a> {
+        SystemConfig {
+            system: Box::new(self.into_system()),
+            stage: Stage::Update,
+            labels: vec![],
+            before: vec![],
+            after: vec![],
+        }
+    }
+}
+
+/// Returned when ordering constraints of systems in one stage contradict each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    pub stage: Stage,
+    /// Names of the systems forming the cycle, each one has to run before the next one and the last one before the first one.
+    pub systems: Vec<String>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Systems in stage {} have cyclic ordering constraints: {} -> {}",
+            self.stage,
+            self.systems.join(" -> "),
+            self.systems[0]
+        )
+    }
+}
+
+impl Error for CycleError {}
+
+/// Returned when ordering constraints of systems in one stage can not be resolved, see [crate::Systems::sort()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    Cycle(CycleError),
+    /// A `before` or `after` constraint of the system names a label which no system has, in any stage.
+    UnknownLabel {
+        stage: Stage,
+        system: String,
+        label: &'static str,
+    },
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(error) => error.fmt(f),
+            Self::UnknownLabel { stage, system, label } => {
+                write!(f, "System {system} in stage {stage} is ordered relative to unknown label {label}")
+            }
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/// Failure of [sort()] with indices of the systems instead of their names.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SortError {
+    /// Systems forming a cycle.
+    Cycle(Vec<usize>),
+    UnknownLabel { system: usize, label: &'static str },
+}
+
+/// Labels of the systems, `before` and `after` constraints can only name those.
+pub(crate) fn labels<'c>(configs: &'c [SystemConfig]) -> impl Iterator<Item = &'static str> + 'c {
+    configs.iter().flat_map(|config| config.labels.iter().copied())
+}
+
+/// Orders the systems of a stage so every `before`/`after` constraint holds, systems which are not constrained
+/// keep the order they were added in. Constraints have to name one of `labels`, which also contains labels of other
+/// stages, those constraints hold anyway.
+pub(crate) fn sort(configs: &[SystemConfig], labels: &HashSet<&'static str>) -> Result<Vec<usize>, SortError> {
+    for (system, config) in configs.iter().enumerate() {
+        if let Some(label) = config.before.iter().chain(&config.after).find(|label| !labels.contains(*label)) {
+            return Err(SortError::UnknownLabel { system, label });
+        }
+    }
+
+    let labelled = |label: &'static str| {
+        configs
+            .iter()
+            .enumerate()
+            .filter(move |(_, config)| config.labels.contains(&label))
+            .map(|(index, _)| index)
+    };
+
+    let mut successors = vec![vec![]; configs.len()];
+    let mut predecessors = vec![vec![]; configs.len()];
+    for (index, config) in configs.iter().enumerate() {
+        let before = config.before.iter().flat_map(|label| labelled(label)).map(|other| (index, other));
+        let after = config.after.iter().flat_map(|label| labelled(label)).map(|other| (other, index));
+        for (first, second) in before.chain(after) {
+            if first != second {
+                successors[first].push(second);
+                predecessors[second].push(first);
+            }
+        }
+    }
+
+    let mut in_degree: Vec<usize> = predecessors.iter().map(Vec::len).collect();
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..configs.len())
+        .filter(|index| in_degree[*index] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(configs.len());
+    while let Some(Reverse(index)) = ready.pop() {
+        order.push(index);
+        for successor in &successors[index] {
+            in_degree[*successor] -= 1;
+            if in_degree[*successor] == 0 {
+                ready.push(Reverse(*successor));
+            }
+        }
+    }
+    if order.len() == configs.len() {
+        return Ok(order);
+    }
+
+    // every system left has a predecessor which is left too, walking back through them has to end in a cycle
+    let mut path = vec![];
+    let mut current = (0..configs.len())
+        .find(|index| in_degree[*index] > 0)
+        .expect("some systems were not sorted");
+    while !path.contains(&current) {
+        path.push(current);
+        current = *predecessors[current]
+            .iter()
+            .find(|predecessor| in_degree[**predecessor] > 0)
+            .expect("unsorted system has an unsorted predecessor");
+    }
+    let start = path.iter().position(|index| *index == current).unwrap_or_default();
+    let mut cycle = path.split_off(start);
+    cycle.reverse();
+    Err(SortError::Cycle(cycle))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        schedule::{labels, sort, SortError},
+        IntoSystemConfig, SystemConfig,
+    };
+
+    fn system() {}
+
+    fn sorted(configs: Vec<SystemConfig<'static>>) -> Result<Vec<usize>, SortError> {
+        sort(&configs, &labels(&configs).collect())
+    }
+
+    #[test]
+    fn unconstrained_systems_keep_insertion_order() {
+        assert_eq!(sorted(vec![system.into_config(), system.into_config(), system.into_config()]), Ok(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn constraints_reorder_systems() {
+        let configs = vec![
+            system.label("c").after("b"),
+            system.label("b"),
+            system.label("a").before("b"),
+            system.after("a").before("c"),
+        ];
+        assert_eq!(sorted(configs), Ok(vec![2, 1, 3, 0]));
+    }
+
+    #[test]
+    fn cycle_is_reported() {
+        let configs = vec![
+            system.label("free"),
+            system.label("a").after("c"),
+            system.label("b").after("a"),
+            system.label("c").after("b"),
+        ];
+        let Err(SortError::Cycle(cycle)) = sorted(configs) else {
+            panic!("the cycle was not found");
+        };
+        assert_eq!(cycle.len(), 3);
+        for (index, system) in cycle.iter().enumerate() {
+            let next = cycle[(index + 1) % cycle.len()];
+            assert_eq!(next, system % 3 + 1);
+        }
+    }
+
+    #[test]
+    fn unknown_label_is_reported() {
+        let configs = vec![system.label("a"), system.after("a").before("missing")];
+        assert_eq!(sorted(configs), Err(SortError::UnknownLabel { system: 1, label: "missing" }));
+
+        // labels of other stages are known
+        let configs = vec![system.after("input")];
+        assert_eq!(sort(&configs, &["input"].into()), Ok(vec![0]));
+    }
+}
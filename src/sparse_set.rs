@@ -0,0 +1,111 @@
+use std::any::Any;
+
+use crate::{
+    change_detection::ComponentTicks,
+    column::{AnyColumn, Column},
+    ComponentCell, Entity,
+};
+
+/// Storage of one component type indexed directly by entity, used for components registered with [crate::StorageType::SparseSet].
+/// Adding and removing such a component never moves the entity between archetypes.
+pub struct SparseSet {
+    sparse: Vec<Option<usize>>, // entity index -> row in `dense`
+    entities: Vec<Entity>,      // owner of every row in `dense`
+    dense: Box<dyn AnyColumn>,
+}
+
+impl SparseSet {
+    pub(crate) fn new(dense: Box<dyn AnyColumn>) -> Self {
+        Self {
+            sparse: vec![],
+            entities: vec![],
+            dense,
+        }
+    }
+
+    fn row(&self, entity: Entity) -> Option<usize> {
+        let row = (*self.sparse.get(entity.index())?)?;
+        (self.entities[row] == entity).then_some(row)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.row(entity).is_some()
+    }
+
+    pub fn insert<T: Any + Send + Sync>(&mut self, entity: Entity, value: T, tick: u32) {
+        let row = self.row(entity);
+        let dense = self
+            .dense
+            .as_any_mut()
+            .downcast_mut::<Column<T>>()
+            .expect("sparse set is created for its component type");
+        if let Some(row) = row {
+            dense.replace(row, value, tick);
+            return;
+        }
+        dense.push(value, tick);
+        self.entities.push(entity);
+        if self.sparse.len() <= entity.index() {
+            self.sparse.resize(entity.index() + 1, None);
+        }
+        self.sparse[entity.index()] = Some(self.entities.len() - 1);
+    }
+
+    /// Makes room for components of `additional` more entities.
+    pub fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+        self.entities.reserve(additional);
+    }
+
+    pub(crate) fn check_change_ticks(&mut self, change_tick: u32) {
+        self.dense.check_change_ticks(change_tick);
+    }
+
+    /// Drops the component of the entity. Returns false if the entity did not have it.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let Some(row) = self.row(entity) else {
+            return false;
+        };
+        self.dense.swap_remove(row);
+        self.forget_row(entity, row);
+        true
+    }
+
+    /// Removes the component of the entity and returns it.
+    pub fn take<T: Any + Send + Sync>(&mut self, entity: Entity) -> Option<T> {
+        let row = self.row(entity)?;
+        let value = self
+            .dense
+            .as_any_mut()
+            .downcast_mut::<Column<T>>()
+            .expect("sparse set is created for its component type")
+            .take(row);
+        self.forget_row(entity, row);
+        Some(value)
+    }
+
+    /// Updates the bookkeeping after the row of the entity was swap removed from `dense`.
+    fn forget_row(&mut self, entity: Entity, row: usize) {
+        self.entities.swap_remove(row);
+        self.sparse[entity.index()] = None;
+        if let Some(swapped) = self.entities.get(row) {
+            self.sparse[swapped.index()] = Some(row);
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<ComponentCell<'_, dyn Any>> {
+        self.dense.get(self.row(entity)?)
+    }
+
+    pub fn typed_get<T: Any>(&self, entity: Entity) -> Option<ComponentCell<'_, T>> {
+        self.typed_dense::<T>()?.get(self.row(entity)?)
+    }
+
+    pub fn typed_ticks<T: Any>(&self, entity: Entity) -> Option<&ComponentTicks> {
+        self.typed_dense::<T>()?.ticks(self.row(entity)?)
+    }
+
+    pub fn typed_dense<T: Any>(&self) -> Option<&Column<T>> {
+        self.dense.as_any().downcast_ref()
+    }
+}
@@ -1,14 +1,135 @@
-use crate::World;
+use std::{any::type_name, collections::HashSet};
 
-pub trait System{
+use crate::{
+    change_detection,
+    schedule::{self, SortError},
+    task_pool::{Task, TaskPool},
+    Access, CycleError, IntoSystemConfig, ScheduleError, Stage, SystemConfig, SystemTicks, World,
+};
+
+pub trait System: Send{
+    /// Name used in errors, the type name by default.
+    fn name(&self) -> String{
+        type_name::<Self>().to_string()
+    }
+    /// Called once by [Systems::init()]. Does not count as a run for [crate::Added] and [crate::Changed].
     fn init(&mut self, _world : &mut World){}
     fn update(&mut self, _world :&mut World){}
+    /// Called every frame by [Systems::render()]. Does not count as a run for [crate::Added] and [crate::Changed].
     fn render(&mut self, _world :&mut World){}
+
+    /// What the system reads and writes when it runs with [System::run_shared()]. None, the default, means the system
+    /// needs the whole world and runs alone with [System::update()], as do systems marked with [Access::main_thread()].
+    /// Function systems get it from their [crate::SystemParam]s.
+    fn access(&mut self, _world: &mut World) -> Option<Access>{
+        None
+    }
+    /// Runs the system instead of [System::update()] when [System::access()] returns Some, possibly in parallel with other systems.
+    /// `ticks` replace [World] change ticks for [crate::Added] and [crate::Changed].
+    fn run_shared(&mut self, _world: &World, _ticks: SystemTicks){}
+    /// Applies changes deferred by [System::run_shared()], called in system order once the systems running in parallel have finished.
+    fn apply_deferred(&mut self, _world: &mut World){}
+}
+
+/// Systems of one stage.
+struct StageSystems<'a>{
+    stage: Stage,
+    configs: Vec<SystemConfig<'a>>,
+    last_runs: Vec<u32>,       // change tick of the previous run of every system, see [crate::Changed]
+    order: Option<Vec<usize>>, // None when systems were added since the last sort
+}
+
+impl<'a> StageSystems<'a>{
+    fn new(stage: Stage) -> Self{
+        Self{
+            stage,
+            configs: vec![],
+            last_runs: vec![],
+            order: None,
+        }
+    }
+
+    /// Runs a system alone so that [crate::Added] and [crate::Changed] report changes made since that system's previous run.
+    fn run_exclusive(&mut self, index: usize, world: &mut World){
+        let last_run = &mut self.last_runs[index];
+        world.last_change_tick = *last_run;
+        self.configs[index].system.update(world);
+        *last_run = world.change_tick;
+        world.change_tick = world.change_tick.wrapping_add(1);
+    }
+
+    /// Runs systems with non conflicting access on the pool. Every system gets the change tick it would get when running alone,
+    /// deferred changes are applied in the order of the batch, so the result does not depend on the order the systems finish in.
+    fn run_batch(&mut self, batch: &mut Vec<usize>, world: &mut World, pool: &TaskPool){
+        let mut ticks = vec![None; self.configs.len()];
+        for (offset, index) in batch.iter().enumerate(){
+            let this_run = world.change_tick.wrapping_add(offset as u32);
+            ticks[*index] = Some(SystemTicks{last_run: self.last_runs[*index], this_run});
+            self.last_runs[*index] = this_run;
+        }
+
+        let shared: &World = world;
+        let tasks: Vec<Task> = self
+            .configs
+            .iter_mut()
+            .zip(ticks)
+            .filter_map(|(config, ticks)| {
+                let ticks = ticks?;
+                let system = config.system.as_mut();
+                Some(Box::new(move || system.run_shared(shared, ticks)) as Task)
+            })
+            .collect();
+        pool.run(tasks);
+
+        for index in batch.drain(..){
+            self.configs[index].system.apply_deferred(world);
+            world.change_tick = world.change_tick.wrapping_add(1);
+        }
+    }
+
+    fn sort(&mut self, labels: &HashSet<&'static str>) -> Result<(), ScheduleError>{
+        let order = schedule::sort(&self.configs, labels).map_err(|error| match error{
+            SortError::Cycle(cycle) => ScheduleError::Cycle(CycleError{
+                stage: self.stage,
+                systems: cycle.iter().map(|index| self.configs[*index].name()).collect(),
+            }),
+            SortError::UnknownLabel{system, label} => ScheduleError::UnknownLabel{
+                stage: self.stage,
+                system: self.configs[system].name(),
+                label,
+            },
+        })?;
+        self.order = Some(order);
+        Ok(())
+    }
 }
 
-#[derive(Default)]
+/**
+Schedule of systems split into stages, which run one after another: [Stage::PreUpdate], [Stage::Update], [Stage::PostUpdate],
+[Stage::Render] and stages added by the user. Inside a stage systems run in the order they were added,
+unless constrained with [IntoSystemConfig::before()] and [IntoSystemConfig::after()].
+
+[Systems::update()] runs the stages before [Stage::Render], [Systems::render()] calls [System::render()] of every system and then
+runs [Stage::Render] with the stages after it. Commands recorded by the systems of a stage are applied when the stage ends.
+
+Systems next to each other in that order run in parallel when their [System::access()] does not conflict and no ordering constraint
+is put between them. Change ticks and commands are handed out in order, so the world ends up the same as when running them one after another.
+ */
 pub struct Systems<'a>{
-    pub systems: Vec<Box<dyn 'a + System>>,
+    stages: Vec<StageSystems<'a>>,
+    task_pool: TaskPool,
+}
+
+impl Default for Systems<'_>{
+    fn default() -> Self{
+        Self{
+            stages: [Stage::PreUpdate, Stage::Update, Stage::PostUpdate, Stage::Render]
+                .into_iter()
+                .map(StageSystems::new)
+                .collect(),
+            task_pool: TaskPool::default(),
+        }
+    }
 }
 
 impl <'a>Systems<'a>{
@@ -16,26 +137,153 @@ impl <'a>Systems<'a>{
         Self::default()
     }
 
-    pub fn with_system(&mut self, system:impl 'a + System) -> &mut Self{
-        self.systems.push(Box::new(system));
+    /// Adds a [System] or a function whose arguments are [crate::SystemParam]s to [Stage::Update]
+    /// or to the stage chosen with [IntoSystemConfig::in_stage()].
+    ///
+    /// Panics if the stage was not added.
+    pub fn with_system<M>(&mut self, system: impl IntoSystemConfig<'a, M>) -> &mut Self{
+        let config = system.into_config();
+        let stage = self.stage_mut(config.stage);
+        stage.configs.push(config);
+        stage.last_runs.push(0);
+        stage.order = None;
+        self
+    }
+
+    /// Sets how many systems can run at the same time, the number of available cores by default. 1 runs every system on the calling thread.
+    pub fn set_threads(&mut self, threads: usize) -> &mut Self{
+        self.task_pool = TaskPool::new(threads);
+        self
+    }
+
+    /// Adds an empty stage running right before `target`. Panics if `target` was not added.
+    pub fn add_stage_before(&mut self, target: Stage, stage: Stage) -> &mut Self{
+        let index = self.stage_index(target);
+        self.stages.insert(index, StageSystems::new(stage));
+        self
+    }
+
+    /// Adds an empty stage running right after `target`. Panics if `target` was not added.
+    pub fn add_stage_after(&mut self, target: Stage, stage: Stage) -> &mut Self{
+        let index = self.stage_index(target);
+        self.stages.insert(index + 1, StageSystems::new(stage));
         self
     }
-    
+
+    /// Resolves ordering constraints of every stage. They are resolved on the first run anyway,
+    /// this is for finding cycles and labels no system has without running anything.
+    pub fn sort(&mut self) -> Result<(), ScheduleError>{
+        for stage in 0..self.stages.len(){
+            self.order(stage)?;
+        }
+        Ok(())
+    }
+
+    /// Calls [System::init()] of every system, stage after stage. Panics if ordering constraints form a cycle or name an unknown label.
     pub fn init(&mut self, world: &mut World){
-        for system in self.systems.iter_mut(){
-            system.init(world);
+        for stage in 0..self.stages.len(){
+            self.run_hooks(stage, world, |system, world| system.init(world));
         }
     }
 
+    /// Runs a frame: swaps the buffers of all [crate::Events] first and then runs every stage before [Stage::Render].
+    /// Panics if ordering constraints form a cycle or name an unknown label.
     pub fn update(&mut self, world: &mut World){
-        for system in self.systems.iter_mut(){
-            system.update(world);
+        if world.check_change_ticks(){
+            for stage in &mut self.stages{
+                for last_run in &mut stage.last_runs{
+                    change_detection::clamp_tick(last_run, world.change_tick);
+                }
+            }
+        }
+        world.update_events();
+        for stage in 0..self.stage_index(Stage::Render){
+            self.run_update(stage, world);
         }
     }
 
+    /// Calls [System::render()] of every system and then runs [Stage::Render] and the stages after it.
+    /// Panics if ordering constraints form a cycle or name an unknown label.
     pub fn render(&mut self, world: &mut World){
-        for system in self.systems.iter_mut(){
-            system.render(world);
+        for stage in 0..self.stages.len(){
+            self.run_hooks(stage, world, |system, world| system.render(world));
+        }
+        for stage in self.stage_index(Stage::Render)..self.stages.len(){
+            self.run_update(stage, world);
+        }
+    }
+
+    /// Runs systems of a single stage. Panics if the stage was not added or its ordering constraints can not be resolved.
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World){
+        let stage = self.stage_index(stage);
+        self.run_update(stage, world);
+    }
+
+    fn stage_index(&self, stage: Stage) -> usize{
+        self.stages
+            .iter()
+            .position(|stage_systems| stage_systems.stage == stage)
+            .unwrap_or_else(|| panic!("Stage {stage} was not added to the systems"))
+    }
+
+    fn stage_mut(&mut self, stage: Stage) -> &mut StageSystems<'a>{
+        let index = self.stage_index(stage);
+        &mut self.stages[index]
+    }
+
+    /// Order of the systems of the stage, sorted again if systems were added to it since the last sort.
+    /// Constraints can name labels of every stage, the ones on systems of other stages hold anyway.
+    fn order(&mut self, stage: usize) -> Result<Vec<usize>, ScheduleError>{
+        if self.stages[stage].order.is_none(){
+            let labels = self.stages.iter().flat_map(|stage| schedule::labels(&stage.configs)).collect();
+            self.stages[stage].sort(&labels)?;
+        }
+        Ok(self.stages[stage].order.clone().unwrap_or_default())
+    }
+
+    /// Calls `f` for every system of the stage one after another. Commands recorded by the systems are applied once all of them have finished.
+    ///
+    /// Change ticks are left alone, so the next update of every system still sees the changes made since its previous update.
+    fn run_hooks(&mut self, stage: usize, world: &mut World, mut f: impl FnMut(&mut dyn System, &mut World)){
+        let order = self.order(stage).unwrap_or_else(|error| panic!("{error}"));
+        let stage = &mut self.stages[stage];
+        for index in order{
+            f(stage.configs[index].system.as_mut(), world);
+        }
+        world.apply_commands();
+    }
+
+    /// Updates every system of the stage, grouping systems which can run in parallel into batches.
+    /// Commands recorded by the systems are applied once all of them have finished.
+    fn run_update(&mut self, stage: usize, world: &mut World){
+        let pool = self.task_pool;
+        let order = self.order(stage).unwrap_or_else(|error| panic!("{error}"));
+        let stage = &mut self.stages[stage];
+        let mut batch = vec![];
+        let mut batch_access: Vec<Access> = vec![];
+        for index in order{
+            let access = stage.configs[index]
+                .system
+                .access(world)
+                .filter(|access| !access.is_main_thread());
+            let fits = access.as_ref().is_some_and(|access| {
+                batch.iter().zip(&batch_access).all(|(other, other_access)| {
+                    !access.conflicts_with(other_access) && !stage.configs[index].is_ordered_with(&stage.configs[*other])
+                })
+            });
+            if !fits{
+                stage.run_batch(&mut batch, world, &pool);
+                batch_access.clear();
+            }
+            match access{
+                Some(access) => {
+                    batch.push(index);
+                    batch_access.push(access);
+                }
+                None => stage.run_exclusive(index, world),
+            }
         }
+        stage.run_batch(&mut batch, world, &pool);
+        world.apply_commands();
     }
 }
@@ -0,0 +1,304 @@
+use std::{
+    any::{type_name, Any},
+    ops::{Deref, DerefMut},
+    sync::PoisonError,
+};
+
+use crate::{
+    command::Command, Access, Commands, EventCursor, EventReader, EventWriter, Events, NonSend, NonSendMut, Query, QueryData,
+    QueryFilter, Res, ResMut, SystemTicks, World,
+};
+
+/**
+Argument of a function system, fetched from the [World] every time the system runs.
+
+Implemented for [Query], [Res], [ResMut], [NonSend], [NonSendMut], [Commands], [EventReader], [EventWriter], [Local], `&World` and tuples of those.
+
+Example:
+```
+use wgtr_ecs::*;
+#[derive(Component)]
+struct Position(f32);
+#[derive(Component)]
+struct Velocity(f32);
+struct Time(f32);
+
+fn movement(query: Query<(&mut Position, &Velocity)>, time: Res<Time>) {
+    for (mut position, velocity) in query.run() {
+        position.0 += velocity.0 * time.0;
+    }
+}
+
+let mut world = World::new();
+world.register_component::<Position>();
+world.register_component::<Velocity>();
+world.add_resource(Time(0.5));
+world.create_entity()
+    .with_component(Position(0.0)).unwrap()
+    .with_component(Velocity(2.0)).unwrap();
+
+let mut systems = Systems::new();
+systems.with_system(movement);
+systems.update(&mut world);
+assert_eq!(world.query::<&Position>().run()[0].0, 1.0);
+```
+ */
+pub trait SystemParam {
+    /// Data kept by the system between runs, for example the cursor of an [EventReader].
+    type State: Send;
+    type Item<'w, 's>;
+
+    fn init_state(world: &mut World) -> Self::State;
+
+    /// Adds what the parameter reads and writes, systems whose parameters do not conflict run in parallel.
+    fn access(access: &mut Access);
+
+    /// `ticks` are the change ticks of the running system, see [crate::Changed].
+    fn get_param<'w, 's>(state: &'s mut Self::State, world: &'w World, ticks: SystemTicks) -> Self::Item<'w, 's>;
+
+    /// Applies changes deferred while the system ran, called in system order once the systems running in parallel have finished.
+    fn apply(_state: &mut Self::State, _world: &mut World) {}
+}
+
+/// The type a [SystemParam] is fetched as, with the lifetimes of a single run of the system.
+pub type SystemParamItem<'w, 's, P> = <P as SystemParam>::Item<'w, 's>;
+
+impl<D: QueryData + 'static, F: QueryFilter + 'static> SystemParam for Query<'_, D, F> {
+    type State = ();
+    type Item<'w, 's> = Query<'w, D, F>;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        D::access(access);
+        F::access(access);
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, ticks: SystemTicks) -> Self::Item<'w, 's> {
+        Query::new(world, ticks)
+    }
+}
+
+/// Panics if the resource does not exist or is borrowed mutably by another parameter of the system.
+impl<T: Any> SystemParam for Res<'_, T> {
+    type State = ();
+    type Item<'w, 's> = Res<'w, T>;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        access.read_resource::<T>();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+            .get_resource()
+            .unwrap_or_else(|| panic!("Resource {} requested by a system does not exist", type_name::<T>()))
+    }
+}
+
+/// Panics if the resource does not exist or is borrowed by another parameter of the system.
+impl<T: Any> SystemParam for ResMut<'_, T> {
+    type State = ();
+    type Item<'w, 's> = ResMut<'w, T>;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        access.write_resource::<T>();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+            .get_resource_mut()
+            .unwrap_or_else(|| panic!("Resource {} requested by a system does not exist", type_name::<T>()))
+    }
+}
+
+/// Commands are recorded into a queue of the system and moved to the world when the system finishes,
+/// so they are applied in system order even when systems run in parallel.
+impl SystemParam for Commands<'_, '_> {
+    type State = Vec<Command>;
+    type Item<'w, 's> = Commands<'w, 's>;
+
+    fn init_state(_world: &mut World) -> Self::State {
+        vec![]
+    }
+
+    fn access(access: &mut Access) {
+        access.commands();
+    }
+
+    fn get_param<'w, 's>(state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        Commands::new(world, Some(state))
+    }
+
+    fn apply(state: &mut Self::State, world: &mut World) {
+        world.command_queue.get_mut().unwrap_or_else(PoisonError::into_inner).append(state);
+    }
+}
+
+/// Adds the events if they were not added with [World::add_event()] yet.
+impl<T: Any + Send + Sync> SystemParam for EventReader<'_, '_, T> {
+    type State = EventCursor<T>;
+    type Item<'w, 's> = EventReader<'w, 's, T>;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.add_event::<T>();
+        EventCursor::new()
+    }
+
+    fn access(access: &mut Access) {
+        access.read_resource::<Events<T>>();
+    }
+
+    fn get_param<'w, 's>(state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        let events = world
+            .get_resource::<Events<T>>()
+            .unwrap_or_else(|| panic!("Events {} were removed from the world", type_name::<T>()));
+        EventReader::new(state, events)
+    }
+}
+
+/// Adds the events if they were not added with [World::add_event()] yet.
+impl<T: Any + Send + Sync> SystemParam for EventWriter<'_, T> {
+    type State = ();
+    type Item<'w, 's> = EventWriter<'w, T>;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.add_event::<T>();
+    }
+
+    fn access(access: &mut Access) {
+        access.write_resource::<Events<T>>();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+            .event_writer()
+            .unwrap_or_else(|| panic!("Events {} were removed from the world", type_name::<T>()))
+    }
+}
+
+/// Panics if the resource does not exist. Systems using it run alone on the thread calling [crate::Systems::update()].
+impl<T: Any> SystemParam for NonSend<'_, T> {
+    type State = ();
+    type Item<'w, 's> = NonSend<'w, T>;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        access.main_thread().read_resource::<T>();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+            .get_non_send_resource()
+            .unwrap_or_else(|| panic!("Non-Send resource {} requested by a system does not exist", type_name::<T>()))
+    }
+}
+
+/// Panics if the resource does not exist. Systems using it run alone on the thread calling [crate::Systems::update()].
+impl<T: Any> SystemParam for NonSendMut<'_, T> {
+    type State = ();
+    type Item<'w, 's> = NonSendMut<'w, T>;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        access.main_thread().write_resource::<T>();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+            .borrow_non_send_resource_mut()
+            .unwrap_or_else(|| panic!("Non-Send resource {} requested by a system does not exist", type_name::<T>()))
+    }
+}
+
+/// Can borrow anything, including resources and components mutably through [World::get_resource_mut()] and queries,
+/// and may record commands with [World::commands()]. Systems taking it never run at the same time as other systems.
+impl SystemParam for &World {
+    type State = ();
+    type Item<'w, 's> = &'w World;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn access(access: &mut Access) {
+        access.write_all().commands();
+    }
+
+    fn get_param<'w, 's>(_state: &'s mut Self::State, world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        world
+    }
+}
+
+/// Value owned by a single function system and kept between its runs, starting as `T::default()`.
+pub struct Local<'s, T>(&'s mut T);
+
+impl<T: Default + Send + 'static> SystemParam for Local<'_, T> {
+    type State = T;
+    type Item<'w, 's> = Local<'s, T>;
+
+    fn init_state(_world: &mut World) -> Self::State {
+        T::default()
+    }
+
+    fn access(_access: &mut Access) {}
+
+    fn get_param<'w, 's>(state: &'s mut Self::State, _world: &'w World, _ticks: SystemTicks) -> Self::Item<'w, 's> {
+        Local(state)
+    }
+}
+
+impl<T> Deref for Local<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0
+    }
+}
+
+impl<T> DerefMut for Local<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.0
+    }
+}
+
+macro_rules! impl_system_param_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($name: SystemParam),*> SystemParam for ($($name,)*) {
+            type State = ($($name::State,)*);
+            type Item<'w, 's> = ($($name::Item<'w, 's>,)*);
+
+            fn init_state(world: &mut World) -> Self::State {
+                ($($name::init_state(world),)*)
+            }
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn get_param<'w, 's>(state: &'s mut Self::State, world: &'w World, ticks: SystemTicks) -> Self::Item<'w, 's> {
+                let ($($name,)*) = state;
+                ($($name::get_param($name, world, ticks),)*)
+            }
+
+            fn apply(state: &mut Self::State, world: &mut World) {
+                let ($($name,)*) = state;
+                $($name::apply($name, world);)*
+            }
+        }
+    };
+}
+
+impl_system_param_tuple!();
+impl_system_param_tuple!(A);
+impl_system_param_tuple!(A, B);
+impl_system_param_tuple!(A, B, C);
+impl_system_param_tuple!(A, B, C, D);
+impl_system_param_tuple!(A, B, C, D, E);
+impl_system_param_tuple!(A, B, C, D, E, F);
+impl_system_param_tuple!(A, B, C, D, E, F, G);
+impl_system_param_tuple!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,77 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+/// Work run by the [TaskPool], borrowing from the caller for `'a`.
+pub(crate) type Task<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+/// Runs batches of tasks on scoped threads, the calling thread takes tasks too.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TaskPool {
+    threads: usize,
+}
+
+impl Default for TaskPool {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl TaskPool {
+    /// Pool running at most `threads` tasks at the same time, 0 is treated as 1.
+    pub(crate) fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+        }
+    }
+
+    /// Runs every task and returns once all of them have finished. A panic in a task is resumed on the calling thread.
+    pub(crate) fn run(&self, tasks: Vec<Task<'_>>) {
+        if self.threads == 1 || tasks.len() <= 1 {
+            tasks.into_iter().for_each(|task| task());
+            return;
+        }
+
+        let workers = self.threads.min(tasks.len()) - 1;
+        let tasks = Mutex::new(tasks.into_iter());
+        let work = || loop {
+            let task = tasks.lock().unwrap_or_else(PoisonError::into_inner).next();
+            match task {
+                Some(task) => task(),
+                None => break,
+            }
+        };
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(work);
+            }
+            work();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{Task, TaskPool};
+
+    #[test]
+    fn every_task_runs_once() {
+        for threads in [0, 1, 4] {
+            let counters: Vec<AtomicUsize> = (0..10).map(|_| AtomicUsize::new(0)).collect();
+            let tasks = counters
+                .iter()
+                .map(|counter| {
+                    Box::new(move || {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }) as Task
+                })
+                .collect();
+            TaskPool::new(threads).run(tasks);
+            assert!(counters.iter().all(|counter| counter.load(Ordering::Relaxed) == 1));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Position(u32);
+#[derive(Component)]
+#[component(storage = "sparse_set")]
+struct Bullet;
+#[derive(Component)]
+#[component(on_insert = spawn_trail)]
+struct Smoke;
+#[derive(Component)]
+struct Trail(Entity);
+
+fn spawn_trail(world: &mut World, entity: Entity) {
+    world.commands().spawn().insert(Trail(entity));
+    world
+        .get_resource::<AtomicUsize>()
+        .unwrap()
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn spawn_batch_returns_handles_in_order() {
+    let mut world = World::with_capacity(100);
+    let bullets = world.spawn_batch((0..100).map(|index| (Position(index), Bullet)));
+
+    assert_eq!(bullets.len(), 100);
+    for (index, bullet) in bullets.iter().enumerate() {
+        let position = world.query_filtered::<&Position, With<Bullet>>().get(*bullet).unwrap();
+        assert_eq!(position.0, index as u32);
+    }
+    assert_eq!(world.query::<&Position>().run().len(), 100);
+}
+
+#[test]
+fn spawn_batch_reuses_free_slots() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let first = world.spawn(Position(0));
+    let second = world.spawn(Position(0));
+    world.remove_entity(first)?;
+    world.remove_entity(second)?;
+    world.reserve_entities(10);
+
+    let spawned = world.spawn_batch(vec![Position(1), Position(2), Position(3)]);
+    assert_eq!(spawned[0].index(), second.index());
+    assert_eq!(spawned[1].index(), first.index());
+    assert_eq!(spawned[2].index(), 2);
+    assert!(spawned.iter().all(|entity| world.is_alive(*entity)));
+    assert!(!world.is_alive(first) && !world.is_alive(second));
+    Ok(())
+}
+
+#[test]
+fn try_spawn_batch_rejects_duplicate_components() {
+    let mut world = World::new();
+    let error = world.try_spawn_batch((0..10).map(|index| (Position(index), Position(index)))).unwrap_err();
+    assert!(matches!(error, EcsError::DuplicateComponent { .. }));
+    assert!(world.query::<Entity>().run().is_empty());
+    assert_eq!(world.spawn(Position(0)).index(), 0);
+}
+
+#[test]
+fn spawn_batch_of_nothing() {
+    let mut world = World::new();
+    assert!(world.spawn_batch(std::iter::empty::<Position>()).is_empty());
+    assert!(world.spawn_batch(Vec::<()>::new()).is_empty());
+}
+
+#[test]
+fn hooks_run_for_every_spawned_entity() {
+    let mut world = World::new();
+    world.add_resource(AtomicUsize::new(0));
+
+    let smokes = world.spawn_batch((0..5).map(|_| Smoke));
+    world.apply_commands();
+
+    assert_eq!(world.get_resource::<AtomicUsize>().unwrap().load(Ordering::Relaxed), 5);
+    let mut trails: Vec<Entity> = world.query::<&Trail>().run().iter().map(|trail| trail.0).collect();
+    trails.sort();
+    assert_eq!(trails, smokes);
+}
@@ -0,0 +1,202 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Speed(u32);
+#[derive(Component)]
+#[component(storage = "sparse_set")]
+struct Stunned;
+#[derive(Component)]
+struct Player;
+
+#[derive(Bundle)]
+struct Movement {
+    speed: Speed,
+    stunned: Stunned,
+}
+
+#[derive(Bundle)]
+struct PlayerBundle {
+    health: Health,
+    movement: Movement,
+    player: Player,
+}
+
+#[derive(Bundle)]
+struct Pair(Health, Speed);
+
+#[test]
+fn spawn_tuples_and_derived_bundles() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let enemy = world.spawn((Health(50), Speed(5)));
+    let player = world.spawn(PlayerBundle {
+        health: Health(100),
+        movement: Movement {
+            speed: Speed(10),
+            stunned: Stunned,
+        },
+        player: Player,
+    });
+    let pair = world.spawn(Pair(Health(1), Speed(1)));
+    let empty = world.spawn(());
+
+    assert_eq!(
+        world.query::<(&Health, &Speed)>().get(enemy).map(|(h, s)| (h.0, s.0))?,
+        (50, 5)
+    );
+    assert_eq!(
+        world.query::<(&Health, &Speed)>().get(pair).map(|(h, s)| (h.0, s.0))?,
+        (1, 1)
+    );
+    let players = world
+        .query_filtered::<(Entity, &Health), (With<Player>, With<Stunned>)>()
+        .run();
+    assert_eq!(players.len(), 1);
+    assert_eq!((players[0].0, players[0].1 .0), (player, 100));
+    assert!(world.is_alive(empty));
+    Ok(())
+}
+
+#[test]
+fn add_bundle_replaces_existing_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+
+    world.add_bundle((Health(20), Speed(2), Stunned), entity)?;
+    world.add_bundle(Speed(3), entity)?;
+
+    let (health, speed) = world.query_filtered::<(&Health, &Speed), With<Stunned>>().get(entity)?;
+    assert_eq!((health.0, speed.0), (20, 3));
+    assert_eq!(world.query::<&Health>().run().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn remove_bundle_skips_missing_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn((Health(10), Speed(1), Stunned));
+    let other = world.spawn((Health(20), Speed(2), Stunned));
+
+    world.remove_bundle::<(Speed, Stunned, Player)>(entity)?;
+    assert_eq!(world.query::<&Health>().get(entity)?.0, 10);
+    assert_eq!(
+        world.query::<&Speed>().get(entity).err(),
+        Some(EcsError::QueryMismatch(entity))
+    );
+    assert!(world.query_filtered::<Entity, With<Stunned>>().get(entity).is_err());
+
+    world.remove_bundle::<Movement>(other)?;
+    assert_eq!(
+        world
+            .query_filtered::<Entity, Or<(With<Speed>, With<Stunned>)>>()
+            .run()
+            .len(),
+        0
+    );
+    Ok(())
+}
+
+#[test]
+fn failed_bundle_leaves_world_untouched() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    world.remove_entity(entity)?;
+
+    assert_eq!(
+        world.add_bundle((Health(1), Speed(1)), entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(
+        world.remove_bundle::<(Health, Speed)>(entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert!(world.query::<&Health>().run().is_empty());
+    assert!(world.query::<&Speed>().run().is_empty());
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "contains the same component more than once")]
+fn duplicate_components_panic() {
+    let mut world = World::new();
+    world.spawn((Health(1), Speed(1), Health(2)));
+}
+
+#[test]
+fn duplicate_components_are_errors() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    let duplicate = EcsError::DuplicateComponent {
+        type_name: std::any::type_name::<Speed>(),
+    };
+
+    assert_eq!(
+        world.add_bundle((Speed(1), Health(20), Speed(2)), entity),
+        Err(duplicate.clone())
+    );
+    world.entity_mut(entity)?.insert(Pair(Health(30), Speed(3)))?;
+    let movement = Movement {
+        speed: Speed(4),
+        stunned: Stunned,
+    };
+    assert_eq!(
+        world.entity_mut(entity)?.insert((movement, Speed(5))).err(),
+        Some(duplicate.clone())
+    );
+    assert_eq!(world.try_spawn((Speed(1), Speed(2))).err(), Some(duplicate));
+
+    assert_eq!(world.get::<Health>(entity)?.0, 30);
+    assert_eq!(world.get::<Speed>(entity)?.0, 3);
+    assert!(!world.entity(entity)?.contains::<Stunned>());
+    assert_eq!(world.query::<&Speed>().run().len(), 1);
+    Ok(())
+}
+
+#[derive(Component)]
+#[component(on_insert = count_insert, on_remove = count_remove)]
+struct Counted;
+
+#[derive(Default)]
+struct Counters {
+    inserted: AtomicUsize,
+    removed: AtomicUsize,
+}
+
+fn count_insert(world: &mut World, _: Entity) {
+    world
+        .get_resource::<Counters>()
+        .unwrap()
+        .inserted
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+fn count_remove(world: &mut World, _: Entity) {
+    world
+        .get_resource::<Counters>()
+        .unwrap()
+        .removed
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn bundles_call_hooks_through_commands() {
+    let mut world = World::new();
+    world.add_resource(Counters::default());
+
+    let entity = world.commands().spawn().insert((Counted, Health(1))).id();
+    world.apply_commands();
+    world
+        .commands()
+        .entity(entity)
+        .remove::<(Counted, Health)>()
+        .remove::<Counted>();
+    world.apply_commands();
+
+    let counters = world.get_resource::<Counters>().unwrap();
+    assert_eq!(counters.inserted.load(Ordering::Relaxed), 1);
+    assert_eq!(counters.removed.load(Ordering::Relaxed), 1);
+    assert!(world.query::<&Health>().run().is_empty());
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Poisoned;
+
+#[test]
+fn added_and_changed_since_clear_trackers() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component_with_storage::<Poisoned>(StorageType::SparseSet)?;
+
+    let first = world.create_entity().with_component(Health(100))?.id();
+    let second = world.create_entity().with_component(Health(50))?.id();
+
+    let mut added = world.query_filtered::<Entity, Added<Health>>().run();
+    added.sort();
+    assert_eq!(added, vec![first, second]);
+
+    world.clear_trackers();
+    assert!(world.query_filtered::<Entity, Added<Health>>().run().is_empty());
+    assert!(world.query_filtered::<Entity, Changed<Health>>().run().is_empty());
+
+    world.query::<&mut Health>().get(second)?.0 -= 10;
+    world.add_component(Poisoned, first)?;
+    assert_eq!(world.query_filtered::<Entity, Changed<Health>>().run(), vec![second]);
+    assert!(world.query_filtered::<Entity, Added<Health>>().run().is_empty());
+    assert_eq!(world.query_filtered::<Entity, Added<Poisoned>>().run(), vec![first]);
+
+    world.clear_trackers();
+    world.add_component(Health(1), first)?;
+    world.add_component(Poisoned, first)?;
+    assert_eq!(world.query_filtered::<Entity, Changed<Health>>().run(), vec![first]);
+    assert_eq!(world.query_filtered::<Entity, Changed<Poisoned>>().run(), vec![first]);
+    assert!(world.query_filtered::<Entity, Added<Poisoned>>().run().is_empty());
+    Ok(())
+}
+
+#[test]
+fn immutable_access_is_not_a_change() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let entity = world.create_entity().with_component(Health(100))?.id();
+    world.clear_trackers();
+
+    assert_eq!(world.query::<&Health>().run().len(), 1);
+    for entity in world.dynamic_query().with_component::<Health>()?.run_entity() {
+        assert_eq!(entity.get_component::<Health>()?.0, 100);
+    }
+    assert!(world.query_filtered::<Entity, Changed<Health>>().run().is_empty());
+
+    for query_entity in world.dynamic_query().with_component::<Health>()?.run_entity() {
+        query_entity.get_component_mut::<Health>()?.0 += 1;
+    }
+    assert_eq!(world.query_filtered::<Entity, Changed<Health>>().run(), vec![entity]);
+    Ok(())
+}
+
+#[test]
+fn changes_are_relative_to_last_run_of_system() -> Result<(), EcsError> {
+    struct Damage;
+    impl System for Damage {
+        fn update(&mut self, world: &mut World) {
+            for mut health in world.query::<&mut Health>().run() {
+                health.0 -= 1;
+            }
+        }
+    }
+
+    struct Observer(Arc<Mutex<Vec<(usize, usize)>>>);
+    impl System for Observer {
+        fn update(&mut self, world: &mut World) {
+            let added = world.query_filtered::<Entity, Added<Health>>().run().len();
+            let changed = world.query_filtered::<Entity, Changed<Health>>().run().len();
+            self.0.lock().unwrap().push((added, changed));
+        }
+    }
+
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.create_entity().with_component(Health(100))?;
+    world.create_entity().with_component(Health(100))?;
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let mut systems = Systems::new();
+    systems.with_system(Observer(seen.clone()));
+    systems.with_system(Damage);
+    systems.with_system(Observer(seen.clone()));
+
+    systems.update(&mut world);
+    systems.update(&mut world);
+    world.create_entity().with_component(Health(10))?;
+    systems.update(&mut world);
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            (2, 2), // first run of the first observer sees everything
+            (2, 2), // first run of the second observer too
+            (0, 2), // damage of the previous frame
+            (0, 2), // damage of this frame
+            (1, 3), // new entity and damage of the previous frame
+            (1, 3), // new entity and damage of this frame
+        ]
+    );
+    Ok(())
+}
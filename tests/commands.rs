@@ -0,0 +1,134 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Poisoned;
+
+#[test]
+fn despawn_while_iterating() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let alive = world.create_entity().with_component(Health(10))?.id();
+    let dead = world.create_entity().with_component(Health(0))?.id();
+
+    let mut commands = world.commands();
+    for entity in world.dynamic_query().with_component::<Health>()?.run_entity() {
+        if entity.get_component::<Health>()?.0 == 0 {
+            commands.despawn(entity.id);
+        }
+    }
+    assert!(world.is_alive(dead));
+
+    world.apply_commands();
+    assert!(world.is_alive(alive));
+    assert!(!world.is_alive(dead));
+    Ok(())
+}
+
+#[test]
+fn spawned_handles_are_reserved_up_front() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let removed = world.create_entity().id();
+    let kept = world.create_entity().id();
+    world.remove_entity(removed)?;
+
+    let mut commands = world.commands();
+    let first = commands.spawn().insert(Health(1)).id();
+    let second = commands.spawn().insert(Health(2)).id();
+    assert_ne!(first, second);
+    assert_ne!(first, removed);
+    assert!(!world.is_alive(first));
+    assert!(!world.is_alive(second));
+
+    world.apply_commands();
+    assert!(world.is_alive(first));
+    assert!(world.is_alive(second));
+    assert!(world.is_alive(kept));
+    assert_eq!(world.query::<&Health>().get(first)?.0, 1);
+    assert_eq!(world.query::<&Health>().get(second)?.0, 2);
+
+    let third = world.create_entity().id();
+    assert!(![first, second, kept].contains(&third));
+    Ok(())
+}
+
+#[test]
+fn insert_and_remove_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component_with_storage::<Poisoned>(StorageType::SparseSet)?;
+    let entity = world.create_entity().with_component(Health(10))?.id();
+
+    world.commands().entity(entity).insert(Poisoned).remove::<Health>();
+    assert!(world.query::<&Health>().get(entity).is_ok());
+
+    world.apply_commands();
+    assert!(world.query::<&Health>().get(entity).is_err());
+    assert!(world.query::<&Poisoned>().get(entity).is_ok());
+
+    let mut commands = world.commands();
+    commands.despawn(entity);
+    commands.insert(entity, Health(5));
+    world.apply_commands();
+    assert!(!world.is_alive(entity));
+    Ok(())
+}
+
+#[test]
+fn systems_apply_commands_after_update() -> Result<(), EcsError> {
+    struct Poison;
+    impl System for Poison {
+        fn update(&mut self, world: &mut World) {
+            let mut commands = world.commands();
+            for (entity, health) in world.query_filtered::<(Entity, &Health), Without<Poisoned>>().run() {
+                if health.0 < 50 {
+                    commands.insert(entity, Poisoned);
+                }
+            }
+        }
+    }
+
+    struct CountPoisoned(Arc<AtomicUsize>);
+    impl System for CountPoisoned {
+        fn update(&mut self, world: &mut World) {
+            self.0.store(world.query::<&Poisoned>().run().len(), Ordering::Relaxed);
+        }
+    }
+
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Poisoned>();
+    world.create_entity().with_component(Health(10))?;
+    world.create_entity().with_component(Health(100))?;
+
+    let poisoned = Arc::new(AtomicUsize::new(0));
+    let mut systems = Systems::new();
+    systems
+        .with_system(Poison)
+        .with_system(CountPoisoned(poisoned.clone()));
+
+    systems.update(&mut world);
+    assert_eq!(poisoned.load(Ordering::Relaxed), 0);
+    assert_eq!(world.query::<&Poisoned>().run().len(), 1);
+
+    systems.update(&mut world);
+    assert_eq!(poisoned.load(Ordering::Relaxed), 1);
+    Ok(())
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Command failed: Bundle contains the same component more than once")]
+fn failing_commands_panic_in_debug_builds() {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    world.commands().insert(entity, (Poisoned, Poisoned));
+    world.apply_commands();
+}
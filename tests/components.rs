@@ -0,0 +1,143 @@
+use wgtr_ecs::*;
+
+#[derive(Default)]
+struct Log(Vec<String>);
+
+fn log(world: &mut World, message: String) {
+    world.get_resource_mut::<Log>().unwrap().0.push(message);
+}
+
+#[derive(Component)]
+#[component(on_insert = health_inserted, on_remove = health_removed)]
+struct Health(u32);
+
+fn health_inserted(world: &mut World, entity: Entity) {
+    let health = world.query::<&Health>().get(entity).unwrap().0;
+    log(world, format!("inserted {health}"));
+}
+
+fn health_removed(world: &mut World, entity: Entity) {
+    let health = world.query::<&Health>().get(entity).unwrap().0;
+    log(world, format!("removed {health}"));
+}
+
+#[derive(Component)]
+#[component(storage = "sparse_set", on_remove = despawn_when_stunned, name = "stun")]
+struct Stunned;
+
+fn despawn_when_stunned(world: &mut World, entity: Entity) {
+    log(world, "stun removed".to_string());
+    world.commands().despawn(entity);
+}
+
+#[derive(Component)]
+struct Tagged<T: Send + Sync + 'static>(T);
+
+#[test]
+fn hooks_see_the_component() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+
+    let entity = world.create_entity().with_component(Health(10))?.id();
+    world.add_component(Health(20), entity)?;
+    world.remove_component::<Health>(entity)?;
+    world.remove_component::<Health>(entity)?; // already removed, no hook
+    world.add_component(Health(30), entity)?;
+    world.remove_entity(entity)?;
+
+    let log = world.get_resource::<Log>().unwrap();
+    assert_eq!(
+        log.0,
+        ["inserted 10", "inserted 20", "removed 20", "inserted 30", "removed 30"]
+    );
+    Ok(())
+}
+
+#[test]
+fn hooks_run_for_commands() {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+
+    let entity = world.commands().spawn().insert(Health(5)).id();
+    world.commands().despawn(entity);
+    world.apply_commands();
+
+    assert!(!world.is_alive(entity));
+    assert_eq!(world.get_resource::<Log>().unwrap().0, ["inserted 5", "removed 5"]);
+}
+
+#[test]
+fn hook_can_remove_the_entity() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+
+    let entity = world
+        .create_entity()
+        .with_component(Health(1))?
+        .with_component(Stunned)?
+        .id();
+    world.remove_component::<Stunned>(entity)?;
+    assert!(world.is_alive(entity));
+    world.apply_commands();
+    assert!(!world.is_alive(entity));
+
+    let entity = world.create_entity().with_component(Stunned)?.id();
+    world.remove_entity(entity)?;
+    world.apply_commands(); // despawning again is skipped
+    assert!(!world.is_alive(entity));
+
+    let log = world.get_resource::<Log>().unwrap();
+    assert_eq!(log.0, ["inserted 1", "stun removed", "removed 1", "stun removed"]);
+    Ok(())
+}
+
+#[test]
+fn serialization_names() {
+    assert_eq!(Health::serialization_name(), "components::Health");
+    assert_eq!(Stunned::serialization_name(), "stun");
+    assert_eq!(Tagged::<u32>::serialization_name(), "components::Tagged<u32>");
+
+    mod game {
+        pub struct Score<T>(pub T);
+    }
+    impl<T: Send + Sync + 'static> Component for game::Score<T> {}
+    assert_eq!(
+        game::Score::<Health>::serialization_name(),
+        "components::serialization_names::game::Score<components::Health>"
+    );
+}
+
+#[test]
+fn same_named_components_get_distinct_serialization_names() {
+    mod a {
+        #[derive(wgtr_ecs::Component)]
+        pub struct Pos;
+    }
+    mod b {
+        #[derive(wgtr_ecs::Component)]
+        pub struct Pos;
+    }
+    assert_ne!(a::Pos::serialization_name(), b::Pos::serialization_name());
+    assert_ne!(Tagged::<u32>::serialization_name(), Tagged::<&str>::serialization_name());
+}
+
+#[test]
+fn generic_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world
+        .create_entity()
+        .with_component(Tagged(1_u32))?
+        .with_component(Tagged("one"))?;
+    world.create_entity().with_component(Tagged(2_u32))?;
+
+    let mut numbers: Vec<u32> = world
+        .query::<&Tagged<u32>>()
+        .run()
+        .iter()
+        .map(|tagged| tagged.0)
+        .collect();
+    numbers.sort();
+    assert_eq!(numbers, [1, 2]);
+    assert_eq!(world.query::<&Tagged<&str>>().run()[0].0, "one");
+    Ok(())
+}
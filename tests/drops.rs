@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use wgtr_ecs::*;
+
+/// Counts how many of its values were dropped.
+#[derive(Component)]
+struct Tracked(u32, Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.1.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Component)]
+#[component(storage = "sparse_set")]
+struct SparseTracked(Tracked);
+
+#[derive(Component)]
+struct Health(u32);
+
+fn counter() -> Arc<AtomicUsize> {
+    Arc::new(AtomicUsize::new(0))
+}
+
+fn drops(counter: &Arc<AtomicUsize>) -> usize {
+    counter.load(Ordering::Relaxed)
+}
+
+#[test]
+fn remove_component_drops_right_away() -> Result<(), EcsError> {
+    let dropped = counter();
+    let mut world = World::new();
+    let entity = world.spawn((
+        Health(1),
+        Tracked(1, dropped.clone()),
+        SparseTracked(Tracked(2, dropped.clone())),
+    ));
+
+    world.remove_component::<Tracked>(entity)?;
+    assert_eq!(drops(&dropped), 1);
+    world.remove_component::<SparseTracked>(entity)?;
+    assert_eq!(drops(&dropped), 2);
+
+    world.add_component(Tracked(3, dropped.clone()), entity)?;
+    assert_eq!(world.query::<&Tracked>().get(entity)?.0, 3);
+    assert_eq!(drops(&dropped), 2);
+    Ok(())
+}
+
+#[test]
+fn replacing_drops_the_old_value() -> Result<(), EcsError> {
+    let dropped = counter();
+    let mut world = World::new();
+    let entity = world.spawn(Tracked(1, dropped.clone()));
+
+    world.add_component(Tracked(2, dropped.clone()), entity)?;
+    assert_eq!(drops(&dropped), 1);
+    assert_eq!(world.query::<&Tracked>().get(entity)?.0, 2);
+    Ok(())
+}
+
+#[test]
+fn remove_entity_drops_every_component() -> Result<(), EcsError> {
+    let dropped = counter();
+    let mut world = World::new();
+    let entity = world.spawn((Tracked(1, dropped.clone()), SparseTracked(Tracked(2, dropped.clone()))));
+    let other = world.spawn(Tracked(3, dropped.clone()));
+
+    world.remove_entity(entity)?;
+    assert_eq!(drops(&dropped), 2);
+    world.commands().despawn(other);
+    world.apply_commands();
+    assert_eq!(drops(&dropped), 3);
+    assert!(world.query::<&Tracked>().run().is_empty());
+    Ok(())
+}
+
+#[test]
+fn take_component_hands_the_value_back() -> Result<(), EcsError> {
+    let dropped = counter();
+    let mut world = World::new();
+    let entity = world.spawn((
+        Health(1),
+        Tracked(1, dropped.clone()),
+        SparseTracked(Tracked(2, dropped.clone())),
+    ));
+    let other = world.spawn((Health(2), Tracked(3, dropped.clone())));
+
+    let taken = world.take_component::<Tracked>(entity).unwrap();
+    assert_eq!(taken.0, 1);
+    assert_eq!(drops(&dropped), 0);
+    let sparse = world.take_component::<SparseTracked>(entity).unwrap();
+    assert_eq!(sparse.0 .0, 2);
+    assert_eq!(drops(&dropped), 0);
+
+    assert!(world.take_component::<Tracked>(entity).is_none());
+    assert!(world.take_component::<SparseTracked>(entity).is_none());
+    assert_eq!(world.query::<&Health>().get(entity)?.0, 1);
+    assert_eq!(world.query::<&Tracked>().get(other)?.0, 3);
+
+    drop(taken);
+    drop(sparse);
+    assert_eq!(drops(&dropped), 2);
+
+    world.remove_entity(other)?;
+    assert_eq!(drops(&dropped), 3);
+    assert!(world.take_component::<Health>(other).is_none());
+    Ok(())
+}
+
+#[test]
+fn dropping_the_world_drops_components() {
+    let dropped = counter();
+    let mut world = World::new();
+    world.spawn_batch((0..10).map(|index| Tracked(index, dropped.clone())));
+    world.spawn(SparseTracked(Tracked(10, dropped.clone())));
+
+    drop(world);
+    assert_eq!(drops(&dropped), 11);
+}
@@ -1,7 +1,7 @@
 use wgtr_ecs::*;
 
 #[test]
-fn create_entity() -> Result<(), &'static str> {
+fn create_entity() -> Result<(), EcsError> {
     let mut world = World::new();
     world.register_component::<Health>();
     world.register_component::<Speed>();
@@ -14,42 +14,44 @@ fn create_entity() -> Result<(), &'static str> {
 }
 
 #[test]
-fn delete_component_from_entity() -> Result<(), &'static str> {
+fn delete_component_from_entity() -> Result<(), EcsError> {
     let mut world = World::new();
     world.register_component::<Health>();
     world.register_component::<Speed>();
 
-    world
+    let first = world
         .create_entity()
         .with_component(Health(100))?
-        .with_component(Speed(10))?;
-    world
+        .with_component(Speed(10))?
+        .id();
+    let second = world
         .create_entity()
         .with_component(Health(100))?
-        .with_component(Speed(10))?;
-    world.remove_component::<Health>(0)?;
+        .with_component(Speed(10))?
+        .id();
+    world.remove_component::<Health>(first)?;
     let query = world
-        .query()
+        .dynamic_query()
         .with_component::<Health>()?
         .with_component::<Speed>()?
         .run();
     assert_eq!(query.0.len(), 1);
-    assert_eq!(query.0[0], 1);
+    assert_eq!(query.0[0], second);
     Ok(())
 }
 
 #[test]
-fn add_component_to_entity() -> Result<(), &'static str> {
+fn add_component_to_entity() -> Result<(), EcsError> {
     let mut world = World::new();
     world.register_component::<Health>();
     world.register_component::<Speed>();
 
-    world.create_entity().with_component(Health(100))?;
+    let entity = world.create_entity().with_component(Health(100))?.id();
 
-    world.add_component(Speed(22), 0)?;
+    world.add_component(Speed(22), entity)?;
 
     let query = world
-        .query()
+        .dynamic_query()
         .with_component::<Health>()?
         .with_component::<Speed>()?
         .run();
@@ -60,28 +62,58 @@ fn add_component_to_entity() -> Result<(), &'static str> {
 }
 
 #[test]
-fn delete_entity() -> Result<(), &'static str> {
+fn delete_entity() -> Result<(), EcsError> {
     let mut world = World::new();
     world.register_component::<Health>();
 
-    world.create_entity().with_component(Health(100))?;
+    let first = world.create_entity().with_component(Health(100))?.id();
 
-    world.create_entity().with_component(Health(200))?;
+    let second = world.create_entity().with_component(Health(200))?.id();
 
-    let query = world.query().with_component::<Health>()?.run();
+    let query = world.dynamic_query().with_component::<Health>()?.run();
 
     assert_eq!(query.0.len(), 2);
-    assert_eq!(query.0[0], 0);
+    assert_eq!(query.0[0], first);
 
-    world.remove_entity(0)?;
+    world.remove_entity(first)?;
 
-    let query = world.query().with_component::<Health>()?.run();
+    let query = world.dynamic_query().with_component::<Health>()?.run();
 
     assert_eq!(query.0.len(), 1);
-    assert_eq!(query.0[0], 1);
+    assert_eq!(query.0[0], second);
+
+    Ok(())
+}
+
+#[test]
+fn stale_entity_is_rejected() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Speed>();
+
+    world.create_entity().with_component(Health(100))?;
+    let removed = world.create_entity().with_component(Health(200))?.id();
+    world.remove_entity(removed)?;
+    assert!(!world.is_alive(removed));
+
+    let reused = world.create_entity().with_component(Health(300))?.id();
+    assert_eq!(reused.index(), removed.index());
+    assert_ne!(reused, removed);
+    assert!(world.is_alive(reused));
+
+    assert!(world.add_component(Speed(1), removed).is_err());
+    assert!(world.remove_component::<Health>(removed).is_err());
+    assert!(world.remove_entity(removed).is_err());
+
+    let query = world.dynamic_query().with_component::<Speed>()?.run();
+    assert!(query.0.is_empty());
 
     Ok(())
 }
 
+#[allow(dead_code)]
+#[derive(Component)]
 struct Health(pub u32);
+#[allow(dead_code)]
+#[derive(Component)]
 struct Speed(pub u32);
@@ -0,0 +1,126 @@
+use std::any::Any;
+
+use wgtr_ecs::*;
+
+/// Tiny xorshift generator so the sequences are random but reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// Reference model: every living entity with the health it was spawned with.
+#[derive(Default)]
+struct Model {
+    alive: Vec<(Entity, u32)>,
+    dead: Vec<Entity>,
+    max_alive: usize,
+}
+
+fn healths(world: &World) -> Result<Vec<(Entity, u32)>, EcsError> {
+    let (entities, components) = world.dynamic_query().with_component::<Health>()?.run();
+    let healths: &Vec<ComponentCell<dyn Any>> = &components[0];
+    let mut result: Vec<(Entity, u32)> = entities
+        .into_iter()
+        .zip(healths)
+        .map(|(entity, health)| (entity, health.borrow().downcast_ref::<Health>().unwrap().0))
+        .collect();
+    result.sort();
+    Ok(result)
+}
+
+fn check(world: &World, model: &Model) -> Result<(), EcsError> {
+    let mut expected = model.alive.clone();
+    expected.sort();
+    assert_eq!(healths(world)?, expected);
+
+    for (entity, _) in &model.alive {
+        assert!(world.is_alive(*entity));
+    }
+    for entity in &model.dead {
+        assert!(!world.is_alive(*entity));
+    }
+
+    // every freed slot, including slot 0, has to be reused before the world grows
+    let slots = model
+        .alive
+        .iter()
+        .map(|(entity, _)| entity.index() + 1)
+        .max()
+        .unwrap_or(0);
+    assert!(slots <= model.max_alive);
+    Ok(())
+}
+
+fn run_sequence(seed: u64, steps: usize) -> Result<(), EcsError> {
+    let mut rng = Rng(seed);
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Speed>();
+    let mut model = Model::default();
+
+    for step in 0..steps {
+        if model.alive.is_empty() || rng.below(3) != 0 {
+            let health = step as u32;
+            let mut builder = world.create_entity();
+            builder.with_component(Health(health))?;
+            if rng.below(2) == 0 {
+                builder.with_component(Speed(health))?;
+            }
+            let entity = builder.id();
+            assert!(model.alive.iter().all(|(alive, _)| *alive != entity));
+            assert!(!model.dead.contains(&entity));
+            model.alive.push((entity, health));
+            model.max_alive = model.max_alive.max(model.alive.len());
+        } else {
+            let (entity, _) = model.alive.swap_remove(rng.below(model.alive.len()));
+            world.remove_entity(entity)?;
+            assert!(world.remove_entity(entity).is_err());
+            model.dead.push(entity);
+        }
+        check(&world, &model)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn random_spawn_despawn_sequences_match_model() -> Result<(), EcsError> {
+    for seed in 1..=32_u64 {
+        run_sequence(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15), 300)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn first_slot_is_recycled() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+
+    let first = world.create_entity().with_component(Health(1))?.id();
+    let second = world.create_entity().with_component(Health(2))?.id();
+    world.remove_entity(first)?;
+
+    let reused = world.create_entity().with_component(Health(3))?.id();
+    assert_eq!(reused.index(), first.index());
+    assert_ne!(reused.generation(), first.generation());
+
+    let mut expected = vec![(second, 2), (reused, 3)];
+    expected.sort();
+    assert_eq!(healths(&world)?, expected);
+    Ok(())
+}
+
+#[derive(Component)]
+struct Health(pub u32);
+#[allow(dead_code)]
+#[derive(Component)]
+struct Speed(pub u32);
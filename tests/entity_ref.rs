@@ -0,0 +1,113 @@
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Speed(u32);
+#[derive(Component)]
+#[component(storage = "sparse_set")]
+struct Stunned;
+
+#[test]
+fn entity_ref_reads_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn((Health(10), Stunned));
+    world.spawn(Speed(1));
+
+    let entity_ref = world.entity(entity)?;
+    assert_eq!(entity_ref.id(), entity);
+    assert!(entity_ref.contains::<Health>());
+    assert!(entity_ref.contains::<Stunned>());
+    assert!(!entity_ref.contains::<Speed>());
+    assert_eq!(entity_ref.get::<Health>()?.0, 10);
+    assert!(matches!(
+        entity_ref.get::<Speed>(),
+        Err(EcsError::MissingComponent { .. })
+    ));
+
+    entity_ref.get_mut::<Health>()?.0 = 20;
+    assert_eq!(world.get::<Health>(entity)?.0, 20);
+    Ok(())
+}
+
+#[test]
+fn entity_ref_borrows_at_runtime() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    let entity_ref = world.entity(entity)?;
+
+    let health = entity_ref.get_mut::<Health>()?;
+    assert!(matches!(
+        entity_ref.get::<Health>(),
+        Err(EcsError::AlreadyBorrowed {
+            state: BorrowState::BorrowedMutably,
+            ..
+        })
+    ));
+    drop(health);
+    let _health = entity_ref.get::<Health>()?;
+    assert!(matches!(
+        entity_ref.get_mut::<Health>(),
+        Err(EcsError::AlreadyBorrowed {
+            state: BorrowState::Borrowed,
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn entity_mut_changes_components() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    let other = world.spawn((Health(1), Speed(1)));
+
+    let mut entity_mut = world.entity_mut(entity)?;
+    entity_mut.insert((Speed(5), Stunned))?.remove::<Health>()?;
+    entity_mut.get_mut::<Speed>()?.0 += 1;
+    assert!(!entity_mut.contains::<Health>());
+    assert!(entity_mut.contains::<Stunned>());
+    assert_eq!(entity_mut.get::<Speed>()?.0, 6);
+
+    assert_eq!(world.get::<Speed>(entity)?.0, 6);
+    assert_eq!(world.get::<Speed>(other)?.0, 1);
+    assert_eq!(world.get::<Health>(other)?.0, 1);
+    Ok(())
+}
+
+#[test]
+fn despawned_entities_are_gone() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn((Health(10), Stunned));
+
+    world.entity_mut(entity)?.despawn()?;
+    assert!(!world.is_alive(entity));
+    assert!(world.entity(entity).is_err());
+    assert!(world.entity_mut(entity).is_err());
+    assert_eq!(world.get::<Health>(entity).err(), Some(EcsError::NoSuchEntity(entity)));
+    Ok(())
+}
+
+#[test]
+fn entity_mut_sees_reserved_entities() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.commands().spawn().id();
+
+    assert!(world.entity(entity).is_err());
+    world.entity_mut(entity)?.insert(Health(3))?;
+    assert_eq!(world.get::<Health>(entity)?.0, 3);
+    Ok(())
+}
+
+#[test]
+fn get_mut_marks_components_changed() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let entity = world.spawn(Health(10));
+    world.clear_trackers();
+
+    assert_eq!(world.entity_mut(entity)?.get::<Health>()?.0, 10);
+    assert!(world.query_filtered::<Entity, Changed<Health>>().run().is_empty());
+    world.entity_mut(entity)?.get_mut::<Health>()?.0 += 1;
+    assert_eq!(world.query_filtered::<Entity, Changed<Health>>().run(), vec![entity]);
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use wgtr_ecs::*;
+
+#[derive(Debug, Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Unregistered;
+
+fn world_with_entity() -> (World, Entity) {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let entity = world.create_entity().with_component(Health(100)).unwrap().id();
+    (world, entity)
+}
+
+#[test]
+fn removed_entity_is_an_error() {
+    let (mut world, entity) = world_with_entity();
+    world.remove_entity(entity).unwrap();
+    world.create_entity().with_component(Health(50)).unwrap(); // takes the slot of the removed entity
+
+    assert!(!world.is_alive(entity));
+    assert_eq!(world.remove_entity(entity), Err(EcsError::NoSuchEntity(entity)));
+    assert_eq!(
+        world.add_component(Health(1), entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(
+        world.remove_component::<Health>(entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(
+        world.query::<&Health>().get(entity).err(),
+        Some(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(world.query::<&Health>().run().len(), 1);
+}
+
+#[test]
+fn entity_of_another_world_is_an_error() {
+    let mut other_world = World::new();
+    let entity = (0..10).map(|_| other_world.create_entity().id()).last().unwrap();
+    let (mut world, _) = world_with_entity();
+
+    assert!(!world.is_alive(entity));
+    assert_eq!(
+        world.add_component(Health(1), entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(
+        world.remove_component::<Health>(entity),
+        Err(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(
+        world.query::<Entity>().get(entity).err(),
+        Some(EcsError::NoSuchEntity(entity))
+    );
+    assert_eq!(world.remove_entity(entity), Err(EcsError::NoSuchEntity(entity)));
+}
+
+#[test]
+fn never_inserted_component_is_not_an_error() {
+    let (mut world, entity) = world_with_entity();
+
+    assert_eq!(world.remove_component::<Unregistered>(entity), Ok(()));
+    assert!(world.query::<&Unregistered>().run().is_empty());
+    assert!(world.query::<(&Health, &mut Unregistered)>().run().is_empty());
+    assert!(world
+        .query_filtered::<&Health, With<Unregistered>>()
+        .run()
+        .is_empty());
+    assert_eq!(
+        world
+            .query_filtered::<&Health, Without<Unregistered>>()
+            .run()
+            .len(),
+        1
+    );
+    assert_eq!(world.query::<Option<&Unregistered>>().run().len(), 1);
+    assert_eq!(
+        world.query::<&Unregistered>().get(entity).err(),
+        Some(EcsError::QueryMismatch(entity))
+    );
+    let query = world
+        .dynamic_query()
+        .with_component::<Health>()
+        .unwrap()
+        .with_component::<Unregistered>()
+        .unwrap()
+        .run();
+    assert!(query.0.is_empty());
+    assert_eq!(query.1.len(), 2);
+    let query = world
+        .dynamic_query()
+        .with_component::<Health>()
+        .unwrap()
+        .without_component::<Unregistered>()
+        .unwrap()
+        .run();
+    assert_eq!(query.0, vec![entity]);
+
+    let query_entity = world
+        .dynamic_query()
+        .with_component::<Health>()
+        .unwrap()
+        .run_entity()
+        .remove(0);
+    assert_eq!(
+        query_entity.get_component::<Unregistered>().err(),
+        Some(EcsError::MissingComponent {
+            entity,
+            type_name: "errors::Unregistered"
+        })
+    );
+}
+
+#[test]
+fn conflicting_borrows_are_errors() {
+    let (world, entity) = world_with_entity();
+    let borrowed = EcsError::AlreadyBorrowed {
+        type_name: "errors::Health",
+        state: BorrowState::BorrowedMutably,
+    };
+
+    let health = world.query::<&mut Health>().get(entity).unwrap();
+    assert_eq!(world.query::<&Health>().try_run().err(), Some(borrowed.clone()));
+    assert_eq!(world.query::<&Health>().get(entity).err(), Some(borrowed.clone()));
+    let query_entity = world
+        .dynamic_query()
+        .with_component::<Health>()
+        .unwrap()
+        .run_entity()
+        .remove(0);
+    assert_eq!(query_entity.get_component::<Health>().err(), Some(borrowed));
+    drop(health);
+
+    let error = world.query::<(&mut Health, &Health)>().try_run().unwrap_err();
+    assert_eq!(error.to_string(), "errors::Health is already borrowed mutably");
+    assert_eq!(world.query::<&Health>().try_run().unwrap()[0].0, 100);
+}
+
+#[test]
+fn reserved_entity_accepts_components() {
+    let (mut world, _) = world_with_entity();
+    let reserved = world.commands().spawn().id();
+
+    assert_eq!(world.add_component(Health(10), reserved), Ok(()));
+    assert_eq!(world.query::<&Health>().get(reserved).unwrap().0, 10);
+    assert_eq!(world.remove_component::<Health>(reserved), Ok(()));
+    assert_eq!(
+        world.query::<&Health>().get(reserved).err(),
+        Some(EcsError::QueryMismatch(reserved))
+    );
+}
@@ -0,0 +1,119 @@
+use std::sync::{Arc, Mutex};
+
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+struct Collision(Entity, Entity);
+struct Damage {
+    target: Entity,
+    amount: u32,
+}
+
+struct Physics {
+    frame: u32,
+}
+impl System for Physics {
+    fn update(&mut self, world: &mut World) {
+        self.frame += 1;
+        if self.frame != 1 {
+            return;
+        }
+        let entities = world.query::<Entity>().run();
+        world
+            .event_writer::<Collision>()
+            .unwrap()
+            .send(Collision(entities[0], entities[1]));
+    }
+}
+
+#[derive(Default)]
+struct CollisionDamage {
+    collisions: EventCursor<Collision>,
+}
+impl System for CollisionDamage {
+    fn update(&mut self, world: &mut World) {
+        let events = world.get_resource::<Events<Collision>>().unwrap();
+        let damage: Vec<Damage> = self
+            .collisions
+            .read(&events)
+            .flat_map(|collision| {
+                [
+                    Damage { target: collision.0, amount: 10 },
+                    Damage { target: collision.1, amount: 10 },
+                ]
+            })
+            .collect();
+        world.event_writer::<Damage>().unwrap().send_batch(damage);
+    }
+}
+
+#[derive(Default)]
+struct ApplyDamage {
+    damage: EventCursor<Damage>,
+}
+impl System for ApplyDamage {
+    fn update(&mut self, world: &mut World) {
+        let events = world.get_resource::<Events<Damage>>().unwrap();
+        for damage in self.damage.read(&events) {
+            world.query::<&mut Health>().get(damage.target).unwrap().0 -= damage.amount;
+        }
+    }
+}
+
+#[test]
+fn readers_see_every_event_once_regardless_of_order() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.add_event::<Collision>();
+    world.add_event::<Damage>();
+    world.create_entity().with_component(Health(100))?;
+    world.create_entity().with_component(Health(100))?;
+
+    let mut systems = Systems::new();
+    systems
+        .with_system(ApplyDamage::default()) // runs before the damage is sent
+        .with_system(CollisionDamage::default())
+        .with_system(Physics { frame: 0 });
+
+    for _ in 0..5 {
+        systems.update(&mut world);
+    }
+
+    let healths: Vec<u32> = world.query::<&Health>().run().iter().map(|health| health.0).collect();
+    assert_eq!(healths, vec![90, 90]);
+    Ok(())
+}
+
+#[test]
+fn events_are_dropped_after_two_frames() {
+    struct Counter(EventCursor<u32>, Arc<Mutex<Vec<usize>>>, usize);
+    impl System for Counter {
+        fn update(&mut self, world: &mut World) {
+            self.2 += 1;
+            if self.2 < 3 {
+                return; // starts reading late
+            }
+            let events = world.get_resource::<Events<u32>>().unwrap();
+            self.1.lock().unwrap().push(self.0.read(&events).count());
+        }
+    }
+
+    let mut world = World::new();
+    world.add_event::<u32>();
+    world.add_event::<u32>();
+    let counts = Arc::new(Mutex::new(vec![]));
+    let mut systems = Systems::new();
+    systems.with_system(Counter(EventCursor::new(), counts.clone(), 0));
+
+    world.event_writer::<u32>().unwrap().send(1);
+    systems.update(&mut world);
+    world.event_writer::<u32>().unwrap().send(2);
+    systems.update(&mut world);
+    world.event_writer::<u32>().unwrap().send(3);
+    systems.update(&mut world);
+    systems.update(&mut world);
+
+    assert_eq!(*counts.lock().unwrap(), vec![1, 0]); // only the event sent right before the third frame is left
+    assert!(world.event_writer::<u64>().is_none());
+}
@@ -1,28 +1,34 @@
 use  wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Speed(f32);
+
 #[test]
-fn macros() -> Result<(), &'static str>{
+fn macros() -> Result<(), EcsError>{
     let mut world = World::new();
 
-    world.register_component::<u32>();
-    world.register_component::<f32>();
+    world.register_component::<Health>();
+    world.register_component::<Speed>();
 
     world.create_entity()
-        .with_component(10u32)?
-        .with_component(10.1f32)?;
+        .with_component(Health(10))?
+        .with_component(Speed(10.1))?;
 
-    let mut query = world.query();
-    make_query!(query, u32, f32);
+    let mut query = world.dynamic_query();
+    make_query!(query, Health, Speed);
     for entity in query.run_entity(){
-        let mut f = get_component!(entity, &mut f32);
-        *f += 1.0;
+        let mut speed = get_component!(entity, &mut Speed);
+        speed.0 += 1.0;
     }
-    let mut query = world.query();
-    make_query!(query, u32, f32);
+    let mut query = world.dynamic_query();
+    make_query!(query, Health, Speed);
     for entity in query.run_entity(){
-        let u = get_component!(entity, &u32);
-        let f = get_component!(entity, &f32);
-        assert_eq!(*u, 10);
-        assert_eq!(*f, 11.1);
+        let health = get_component!(entity, &Health);
+        let speed = get_component!(entity, &Speed);
+        assert_eq!(health.0, 10);
+        assert_eq!(speed.0, 11.1);
     }
     Ok(())
 }
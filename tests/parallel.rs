@@ -0,0 +1,220 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use wgtr_ecs::*;
+
+#[derive(Debug, Clone, PartialEq, Component)]
+struct Position(i32);
+#[derive(Debug, Clone, PartialEq, Component)]
+struct Velocity(i32);
+#[derive(Debug, Clone, PartialEq, Component)]
+struct Health(i32);
+#[derive(Debug, Clone, PartialEq, Component)]
+struct Spawned(u32);
+
+#[derive(Default)]
+struct Moved(usize);
+#[derive(Default)]
+struct Frame(u32);
+struct Died(Entity);
+#[derive(Default)]
+struct Deaths(Vec<Entity>);
+
+fn movement(query: Query<(&mut Position, &Velocity)>) {
+    for (mut position, velocity) in query.run() {
+        position.0 += velocity.0;
+    }
+}
+
+fn count_moved(query: Query<&Position, Changed<Position>>, mut moved: ResMut<Moved>) {
+    moved.0 += query.run().len();
+}
+
+fn damage(query: Query<(Entity, &mut Health)>, mut died: EventWriter<Died>) {
+    for (entity, mut health) in query.run() {
+        health.0 -= 3;
+        if health.0 <= 0 {
+            died.send(Died(entity));
+        }
+    }
+}
+
+fn bury(mut died: EventReader<Died>, mut deaths: ResMut<Deaths>, mut commands: Commands) {
+    for Died(entity) in died.read() {
+        deaths.0.push(*entity);
+        commands.despawn(*entity);
+    }
+}
+
+fn spawn_walkers(mut frame: ResMut<Frame>, mut commands: Commands) {
+    frame.0 += 1;
+    commands
+        .spawn()
+        .insert(Position(0))
+        .insert(Velocity(frame.0 as i32))
+        .insert(Health(10));
+}
+
+fn spawn_markers(frame: Res<Frame>, mut commands: Commands) {
+    commands.spawn().insert(Spawned(frame.0));
+}
+
+type Snapshot = (
+    Vec<(Entity, Option<Position>, Option<Health>, Option<Spawned>)>,
+    usize,
+    Vec<Entity>,
+);
+
+fn simulate(threads: usize) -> Snapshot {
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.register_component::<Velocity>();
+    world.register_component::<Health>();
+    world.register_component::<Spawned>();
+    world.add_resource(Moved::default());
+    world.add_resource(Frame::default());
+    world.add_resource(Deaths::default());
+    for index in 0..20 {
+        world
+            .create_entity()
+            .with_component(Position(index))
+            .unwrap()
+            .with_component(Velocity(index % 3))
+            .unwrap()
+            .with_component(Health(index))
+            .unwrap();
+    }
+
+    let mut systems = Systems::new();
+    systems
+        .set_threads(threads)
+        .with_system(movement)
+        .with_system(count_moved)
+        .with_system(damage)
+        .with_system(bury)
+        .with_system(spawn_walkers)
+        .with_system(spawn_markers)
+        .with_system(movement.in_stage(Stage::PostUpdate))
+        .with_system(damage.in_stage(Stage::PostUpdate));
+    for _ in 0..10 {
+        systems.update(&mut world);
+    }
+
+    let entities = world
+        .query::<(Entity, Option<&Position>, Option<&Health>, Option<&Spawned>)>()
+        .run()
+        .into_iter()
+        .map(|(entity, position, health, spawned)| {
+            (
+                entity,
+                position.map(|position| position.clone()),
+                health.map(|health| health.clone()),
+                spawned.map(|spawned| spawned.clone()),
+            )
+        })
+        .collect();
+    let moved = world.get_resource::<Moved>().unwrap().0;
+    let deaths = world.get_resource::<Deaths>().unwrap().0.clone();
+    (entities, moved, deaths)
+}
+
+#[test]
+fn parallel_run_matches_serial_run() {
+    let serial = simulate(1);
+    assert!(!serial.2.is_empty());
+    for threads in [2, 4, 8] {
+        assert_eq!(simulate(threads), serial);
+    }
+}
+
+/// Place where systems wait for each other, they only meet if they run at the same time.
+#[derive(Default)]
+struct Meeting {
+    arrived: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl Meeting {
+    fn meet(&self, timeout: Duration) -> bool {
+        let mut arrived = self.arrived.lock().unwrap();
+        *arrived += 1;
+        self.condvar.notify_all();
+        let (arrived, _) = self
+            .condvar
+            .wait_timeout_while(arrived, timeout, |arrived| *arrived < 2)
+            .unwrap();
+        *arrived >= 2
+    }
+}
+
+#[derive(Default)]
+struct Met(Vec<bool>);
+
+fn meet_and_move(meeting: Res<Meeting>, _query: Query<&mut Position>, mut met: ResMut<Met>) {
+    met.0.push(meeting.meet(Duration::from_secs(1)));
+}
+
+#[test]
+fn systems_with_disjoint_access_run_at_the_same_time() {
+    #[derive(Default)]
+    struct HealerMet(bool);
+
+    fn meet_and_heal(meeting: Res<Meeting>, _query: Query<&mut Health>, mut met: ResMut<HealerMet>) {
+        met.0 = meeting.meet(Duration::from_secs(10));
+    }
+
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.register_component::<Health>();
+    world.add_resource(Meeting::default());
+    world.add_resource(Met::default());
+    world.add_resource(HealerMet::default());
+    let mut systems = Systems::new();
+    systems
+        .set_threads(2)
+        .with_system(meet_and_move)
+        .with_system(meet_and_heal);
+    systems.update(&mut world);
+    assert_eq!(world.get_resource::<Met>().unwrap().0, vec![true]);
+    assert!(world.get_resource::<HealerMet>().unwrap().0);
+}
+
+#[test]
+fn conflicting_systems_run_one_after_another() {
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.add_resource(Meeting::default());
+    world.add_resource(Met::default());
+    let mut systems = Systems::new();
+    systems
+        .set_threads(2)
+        .with_system(meet_and_move)
+        .with_system(meet_and_move);
+    systems.update(&mut world);
+    assert_eq!(world.get_resource::<Met>().unwrap().0, vec![false, true]);
+}
+
+#[test]
+fn world_systems_run_alone() {
+    fn meet_with_world(world: &World) {
+        let met = world.get_resource::<Meeting>().unwrap().meet(Duration::from_millis(200));
+        world.get_resource_mut::<Met>().unwrap().0.push(met);
+    }
+
+    fn meet_and_read(meeting: Res<Meeting>, _query: Query<&Position>) {
+        meeting.meet(Duration::from_millis(200));
+    }
+
+    let mut world = World::new();
+    world.add_resource(Meeting::default());
+    world.add_resource(Met::default());
+    let mut systems = Systems::new();
+    systems
+        .set_threads(2)
+        .with_system(meet_with_world)
+        .with_system(meet_and_read);
+    systems.update(&mut world);
+    assert_eq!(world.get_resource::<Met>().unwrap().0, vec![false]);
+}
@@ -1,9 +1,9 @@
-use std::{any::Any, cell::RefCell, rc::Rc};
+use std::any::Any;
 
 use wgtr_ecs::*;
 
 #[test]
-fn create_query() -> Result<(), &'static str> {
+fn create_query() -> Result<(), EcsError> {
     let mut world = World::new();
     world.register_component::<Health>();
     world.register_component::<Speed>();
@@ -23,12 +23,12 @@ fn create_query() -> Result<(), &'static str> {
         .with_component(Speed(12))?;
 
     let query = world
-        .query()
+        .dynamic_query()
         .with_component::<Health>()?
         .with_component::<Speed>()?
         .run();
-    let healths: &Vec<Rc<RefCell<dyn Any>>> = &query.1[0];
-    let speeds: &Vec<Rc<RefCell<dyn Any>>> = &query.1[1];
+    let healths: &Vec<ComponentCell<dyn Any>> = &query.1[0];
+    let speeds: &Vec<ComponentCell<dyn Any>> = &query.1[1];
 
     assert_eq!(healths.len(), speeds.len());
     assert_eq!(healths.len(), 2);
@@ -44,12 +44,254 @@ fn create_query() -> Result<(), &'static str> {
     let second_healt = borrowed_second_health.downcast_ref::<Health>().unwrap();
     assert_eq!(second_healt.0, 200);
     let mut borrowed_second_speed = speeds[1].borrow_mut();
-    let mut second_speed = borrowed_second_speed.downcast_mut::<Speed>().unwrap();
+    let second_speed = borrowed_second_speed.downcast_mut::<Speed>().unwrap();
     second_speed.0 += 1;
     assert_eq!(second_speed.0, 13);
 
     Ok(())
 }
 
+#[test]
+fn create_typed_query() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Speed>();
+
+    world
+        .create_entity()
+        .with_component(Health(100))?
+        .with_component(Speed(10))?;
+
+    world.create_entity().with_component(Speed(13))?;
+
+    world.create_entity().with_component(Health(130))?;
+
+    world
+        .create_entity()
+        .with_component(Health(200))?
+        .with_component(Speed(12))?;
+
+    for (health, mut speed) in world.query::<(&Health, &mut Speed)>().run() {
+        if health.0 == 200 {
+            speed.0 += 1;
+        }
+    }
+
+    let query = world.query::<(&Health, &Speed)>().run();
+    assert_eq!(query.len(), 2);
+    let (first_health, first_speed) = &query[0];
+    assert_eq!(first_health.0, 100);
+    assert_eq!(first_speed.0, 10);
+    let (second_health, second_speed) = &query[1];
+    assert_eq!(second_health.0, 200);
+    assert_eq!(second_speed.0, 13);
+
+    Ok(())
+}
+
+#[test]
+fn filter_typed_query() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Speed>();
+    world.register_component::<Player>();
+    world.register_component_with_storage::<Dead>(StorageType::SparseSet)?;
+
+    let player = world
+        .create_entity()
+        .with_component(Health(100))?
+        .with_component(Player)?
+        .id();
+    let dead_player = world
+        .create_entity()
+        .with_component(Health(0))?
+        .with_component(Player)?
+        .with_component(Dead)?
+        .id();
+    let monster = world
+        .create_entity()
+        .with_component(Health(50))?
+        .with_component(Speed(5))?
+        .id();
+    let rock = world.create_entity().with_component(Health(1000))?.id();
+
+    let alive = world.query_filtered::<Entity, Without<Dead>>().run();
+    assert_eq!(alive.len(), 3);
+    assert!(!alive.contains(&dead_player));
+
+    let alive_players = world
+        .query_filtered::<Entity, (With<Player>, Without<Dead>)>()
+        .run();
+    assert_eq!(alive_players, vec![player]);
+
+    let mut movers = world
+        .query_filtered::<Entity, Or<(With<Player>, With<Speed>)>>()
+        .run();
+    movers.sort();
+    let mut expected = vec![player, dead_player, monster];
+    expected.sort();
+    assert_eq!(movers, expected);
+
+    let speeds = world.query::<(Entity, &Health, Option<&Speed>)>().run();
+    assert_eq!(speeds.len(), 4);
+    for (entity, _, speed) in speeds {
+        assert_eq!(speed.is_some(), entity == monster);
+    }
+
+    let not_players = world
+        .query_filtered::<&Health, (Without<Player>, Without<Speed>)>()
+        .run();
+    assert_eq!(not_players.len(), 1);
+    assert_eq!(not_players[0].0, 1000);
+    assert!(world.query_filtered::<&Health, Without<Player>>().get(rock).is_ok());
+    assert!(world.query_filtered::<&Health, Without<Player>>().get(player).is_err());
+
+    let query = world
+        .dynamic_query()
+        .with_component::<Health>()?
+        .without_component::<Player>()?
+        .without_component::<Dead>()?
+        .run();
+    assert_eq!(query.0.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn query_more_than_128_component_types() -> Result<(), EcsError> {
+    let mut world = World::new();
+    macro_rules! register_markers {
+        ($($n:literal)*) => { $(world.register_component::<Marker<$n>>();)* };
+    }
+    register_markers!(
+        0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+        33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61
+        62 63 64 65 66 67 68 69 70 71 72 73 74 75 76 77 78 79 80 81 82 83 84 85 86 87 88 89 90
+        91 92 93 94 95 96 97 98 99 100 101 102 103 104 105 106 107 108 109 110 111 112 113 114
+        115 116 117 118 119 120 121 122 123 124 125 126 127 128 129 130 131 132 133 134 135 136
+        137 138 139 140 141 142 143 144 145 146 147 148 149 150 151 152 153 154 155 156 157 158
+        159 160 161 162 163 164 165 166 167 168 169 170 171 172 173 174 175 176 177 178 179 180
+        181 182 183 184 185 186 187 188 189 190 191 192 193 194 195 196 197 198 199
+    );
+    world.register_component::<Health>();
+
+    let entity = world
+        .create_entity()
+        .with_component(Marker::<0>)?
+        .with_component(Marker::<150>)?
+        .with_component(Health(150))?
+        .id();
+    world
+        .create_entity()
+        .with_component(Marker::<150>)?
+        .with_component(Marker::<199>)?;
+    world.create_entity().with_component(Health(10))?;
+
+    let query = world
+        .dynamic_query()
+        .with_component::<Marker<150>>()?
+        .with_component::<Health>()?
+        .run();
+    assert_eq!(query.0, vec![entity]);
+
+    let query = world.dynamic_query().with_component::<Marker<150>>()?.run();
+    assert_eq!(query.0.len(), 2);
+
+    world.remove_component::<Marker<150>>(entity)?;
+    let query = world.dynamic_query().with_component::<Marker<150>>()?.run();
+    assert_eq!(query.0.len(), 1);
+
+    Ok(())
+}
+
+#[derive(Component)]
+struct Marker<const N: usize>;
+#[derive(Component)]
+struct Player;
+#[derive(Component)]
+struct Dead;
+#[derive(Component)]
 struct Health(pub u32);
+#[derive(Component)]
 struct Speed(pub u32);
+
+#[test]
+fn iterate_typed_query() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let first = world.spawn((Health(100), Speed(10)));
+    world.spawn(Speed(13));
+    let second = world.spawn((Health(200), Speed(12), Player));
+    world.clear_trackers();
+
+    let mut query = world.query::<(&Health, &mut Speed)>();
+    for (health, mut speed) in query.iter_mut() {
+        if health.0 == 200 {
+            speed.0 += 1;
+        }
+    }
+    assert_eq!(world.query_filtered::<Entity, Changed<Speed>>().iter().count(), 2);
+
+    let healths: Vec<(Entity, u32)> = world
+        .query::<(Entity, &Health)>()
+        .iter()
+        .map(|(entity, health)| (entity, health.0))
+        .collect();
+    assert_eq!(healths, [(first, 100), (second, 200)]);
+
+    let mut total = 0;
+    world.query::<&Speed>().for_each(|speed| total += speed.0);
+    assert_eq!(total, 36);
+    world
+        .query_filtered::<&mut Speed, With<Player>>()
+        .for_each_mut(|mut speed| speed.0 = 0);
+    assert_eq!(world.get::<Speed>(second)?.0, 0);
+    Ok(())
+}
+
+#[test]
+fn typed_query_iter_is_lazy() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let first = world.spawn(Health(1));
+    let second = world.spawn(Health(2));
+
+    let mut query = world.query::<&mut Health>();
+    let mut iter = query.iter_mut();
+    let mut health = iter.next().unwrap();
+    health.0 += 10;
+    // the second entity is not borrowed until the iterator gets to it
+    assert_eq!(world.get::<Health>(second)?.0, 2);
+    assert!(world.get::<Health>(first).is_err());
+    drop(health);
+
+    assert_eq!(world.get::<Health>(first)?.0, 11);
+    Ok(())
+}
+
+#[test]
+fn try_iter_returns_borrow_errors() -> Result<(), EcsError> {
+    let mut world = World::new();
+    let first = world.spawn(Health(1));
+    world.spawn(Health(2));
+
+    let health = world.get::<Health>(first)?;
+    let mut query = world.query::<&mut Health>();
+    let results = query.try_iter_mut().map(|health| health.map(|health| health.0)).collect::<Vec<_>>();
+    assert!(matches!(
+        results[0],
+        Err(EcsError::AlreadyBorrowed {
+            state: BorrowState::Borrowed,
+            ..
+        })
+    ));
+    assert_eq!(results[1], Ok(2));
+
+    let mut visited = 0;
+    let error = query.try_for_each_mut(|_| visited += 1).unwrap_err();
+    assert!(matches!(error, EcsError::AlreadyBorrowed { .. }));
+    assert_eq!(visited, 0); // stops at the first error
+    drop(health);
+
+    world.query::<&Health>().try_for_each(|health| visited += health.0)?;
+    assert_eq!(visited, 3);
+    Ok(())
+}
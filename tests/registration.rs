@@ -0,0 +1,46 @@
+use wgtr_ecs::*;
+
+#[derive(Component)]
+struct Health(u32);
+#[derive(Component)]
+struct Poisoned;
+
+#[test]
+fn components_register_on_first_insert() -> Result<(), EcsError> {
+    let mut world = World::new();
+    assert!(world.query::<&Health>().run().is_empty());
+
+    let entity = world.create_entity().with_component(Health(100))?.id();
+    world.commands().entity(entity).insert(Poisoned);
+    world.apply_commands();
+
+    let healths = world.query_filtered::<&Health, With<Poisoned>>().run();
+    assert_eq!(healths.len(), 1);
+    assert_eq!(healths[0].0, 100);
+    drop(healths);
+
+    world.remove_component::<Poisoned>(entity)?;
+    assert!(world.query::<&Poisoned>().run().is_empty());
+    Ok(())
+}
+
+#[test]
+fn explicit_registration_chooses_storage() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component_with_storage::<Poisoned>(StorageType::SparseSet)?;
+    let entity = world.create_entity().with_component(Health(100))?.with_component(Poisoned)?.id();
+    world.register_component_with_storage::<Poisoned>(StorageType::SparseSet)?;
+    world.register_component::<Health>();
+
+    assert_eq!(world.query::<(Entity, &Health, &Poisoned)>().run()[0].0, entity);
+    Ok(())
+}
+
+#[test]
+fn registering_another_storage_is_an_error() {
+    let mut world = World::new();
+    world.create_entity().with_component(Health(100)).unwrap();
+    let error = world.register_component_with_storage::<Health>(StorageType::SparseSet).unwrap_err();
+    assert!(matches!(error, EcsError::StorageConflict { storage: StorageType::Table, .. }));
+    assert_eq!(world.query::<&Health>().run().len(), 1);
+}
@@ -1,3 +1,10 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    thread,
+};
+
 use wgtr_ecs::*;
 
 #[test]
@@ -15,7 +22,7 @@ fn create_and_get_resource_mutably() {
 
     world.add_resource(FpsResource(60));
     {
-        let fps = world.get_resource_mut::<FpsResource>().unwrap();
+        let mut fps = world.get_resource_mut::<FpsResource>().unwrap();
         fps.0 += 1;
     }
     let fps = world.get_resource::<FpsResource>().unwrap();
@@ -31,5 +38,222 @@ fn remove_resource() {
     let removed_resource = world.get_resource::<FpsResource>();
     assert!(removed_resource.is_none());
 }
+#[test]
+fn world_moves_between_threads() {
+    let mut world = World::new();
+    world.register_component::<FpsResource>();
+    world.add_resource(FpsResource(60));
+
+    let world = thread::spawn(move || {
+        world.get_resource_mut::<FpsResource>().unwrap().0 += 1;
+        world.create_entity().with_component(FpsResource(30)).unwrap();
+        world
+    })
+    .join()
+    .unwrap();
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 61);
+    assert_eq!(world.query::<&FpsResource>().run()[0].0, 30);
+}
+
+#[test]
+fn non_send_resources_stay_on_their_thread() {
+    let mut world = World::new();
+    let shared = Rc::new(Cell::new(1_u32));
+    world.add_non_send_resource(shared.clone());
+    world.get_non_send_resource_mut::<Rc<Cell<u32>>>().unwrap().set(2);
+    assert_eq!(world.get_non_send_resource::<Rc<Cell<u32>>>().unwrap().get(), 2);
+
+    let other_thread = thread::scope(|scope| {
+        scope
+            .spawn(|| world.get_non_send_resource::<Rc<Cell<u32>>>().is_some())
+            .join()
+    });
+    assert!(other_thread.is_err());
+
+    world.remove_non_send_resource::<Rc<Cell<u32>>>();
+    assert!(world.get_non_send_resource::<Rc<Cell<u32>>>().is_none());
+    assert_eq!(Rc::strong_count(&shared), 1);
+}
+
+#[test]
+fn non_send_resources_in_systems() {
+    struct Window(Rc<Cell<u32>>);
+
+    fn resize(window: NonSend<Window>, mut fps: ResMut<FpsResource>) {
+        fps.0 = window.0.get();
+    }
+    fn redraw(mut window: NonSendMut<Window>) {
+        window.0 = Rc::new(Cell::new(window.0.get() * 2));
+    }
+
+    let mut world = World::new();
+    world.add_resource(FpsResource(0));
+    world.add_non_send_resource(Window(Rc::new(Cell::new(30))));
+    let mut systems = Systems::new();
+    systems.set_threads(4).with_system(resize).with_system(redraw).with_system(resize);
+    systems.update(&mut world);
+
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 60);
+}
+#[test]
+fn borrow_many_resources_while_querying() {
+    struct Gravity(i32);
+    #[derive(Component)]
+    struct Velocity(i32);
+
+    let mut world = World::new();
+    world.register_component::<Velocity>();
+    world.create_entity().with_component(Velocity(0)).unwrap();
+    world.add_resource(FpsResource(60));
+    world.add_resource(Gravity(-10));
+
+    let mut fps = world.get_resource_mut::<FpsResource>().unwrap();
+    let gravity = world.get_resource::<Gravity>().unwrap();
+    for mut velocity in world.query::<&mut Velocity>().run() {
+        velocity.0 += gravity.0;
+        fps.0 -= 1;
+    }
+    let other_gravity = world.get_resource::<Gravity>().unwrap();
+    assert_eq!(other_gravity.0, gravity.0);
+    drop((fps, gravity, other_gravity));
+
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 59);
+    assert_eq!(world.query::<&Velocity>().run()[0].0, -10);
+}
+
+#[test]
+fn conflicting_borrows_name_the_resource() {
+    let mut world = World::new();
+    world.add_resource(FpsResource(60));
+
+    let fps = world.get_resource::<FpsResource>().unwrap();
+    let error = world.try_get_resource_mut::<FpsResource>().err().unwrap();
+    assert!(matches!(
+        error,
+        EcsError::AlreadyBorrowed {
+            state: BorrowState::Borrowed,
+            ..
+        }
+    ));
+    assert_eq!(error.to_string(), "resources::FpsResource is already borrowed");
+    assert!(world.try_get_resource::<FpsResource>().is_ok());
+    drop(fps);
+
+    let _fps = world.get_resource_mut::<FpsResource>().unwrap();
+    let error = world.try_get_resource::<FpsResource>().err().unwrap();
+    assert_eq!(error.to_string(), "resources::FpsResource is already borrowed mutably");
+    assert!(world.try_get_resource_mut::<u32>().unwrap().is_none());
+}
+
+#[test]
+#[should_panic(expected = "resources::FpsResource is already borrowed mutably")]
+fn system_borrowing_resource_twice_panics() {
+    fn conflict(_fps: ResMut<FpsResource>, _same_fps: Res<FpsResource>) {}
+
+    let mut world = World::new();
+    world.add_resource(FpsResource(60));
+    let mut systems = Systems::new();
+    systems.with_system(conflict);
+    systems.update(&mut world);
+}
+#[test]
+fn resource_scope_restores_resource() {
+    #[derive(Component)]
+    struct Frame(u32);
+
+    let mut world = World::new();
+    world.register_component::<Frame>();
+    world.add_resource(FpsResource(60));
+
+    let entity = world.resource_scope(|world, fps: &mut FpsResource| {
+        let entity = world.create_entity().id();
+        if fps.0 == 60 {
+            return Err(entity);
+        }
+        Ok(())
+    });
+    let entity = entity.unwrap().unwrap_err();
+    assert!(world.get_resource::<FpsResource>().is_some());
+
+    let frame = world.resource_scope(|world, fps: &mut FpsResource| {
+        fps.0 += 1;
+        world.add_component(Frame(fps.0), entity).map(|_| fps.0)
+    });
+    assert_eq!(frame, Ok(Ok(61)));
+    assert_eq!(world.query::<&Frame>().run()[0].0, 61);
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 61);
+    assert_eq!(
+        world.resource_scope(|_, _: &mut u32| ()),
+        Err(EcsError::MissingResource { type_name: "u32" })
+    );
+}
+
+#[test]
+fn nested_resource_scope_is_an_error() {
+    let mut world = World::new();
+    world.add_resource(FpsResource(60));
+
+    let nested = world
+        .resource_scope(|world, fps: &mut FpsResource| {
+            fps.0 += 1;
+            world.resource_scope(|_, fps: &mut FpsResource| fps.0 += 1)
+        })
+        .unwrap();
+    assert_eq!(
+        nested,
+        Err(EcsError::AlreadyBorrowed {
+            type_name: "resources::FpsResource",
+            state: BorrowState::Detached
+        })
+    );
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 61);
+}
+
+#[test]
+fn resource_scope_restores_resource_after_panic() {
+    let mut world = World::new();
+    world.add_resource(FpsResource(60));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        world.resource_scope(|_, fps: &mut FpsResource| {
+            fps.0 = 30;
+            panic!("frame dropped");
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(world.get_resource::<FpsResource>().unwrap().0, 30);
+}
+
+#[test]
+fn detached_resource_reports_scope() {
+    fn read_fps(_fps: Res<FpsResource>) {}
+
+    let mut world = World::new();
+    world.add_resource(FpsResource(60));
+    let mut systems = Systems::new();
+    systems.with_system(read_fps);
+
+    world.resource_scope(|world, _: &mut FpsResource| {
+        let error = world.try_get_resource_mut::<FpsResource>().err().unwrap();
+        assert_eq!(
+            error,
+            EcsError::AlreadyBorrowed {
+                type_name: "resources::FpsResource",
+                state: BorrowState::Detached
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "resources::FpsResource is taken out of the world by World::resource_scope"
+        );
+
+        let update = panic::catch_unwind(AssertUnwindSafe(|| systems.update(world)));
+        let message = update.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("FpsResource is taken out of the world"));
+    })
+    .unwrap();
+    assert!(world.try_get_resource::<FpsResource>().unwrap().is_some());
+}
 
+#[derive(Component)]
 struct FpsResource(pub u32);
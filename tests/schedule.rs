@@ -0,0 +1,147 @@
+use wgtr_ecs::*;
+
+#[derive(Default)]
+struct Log(Vec<&'static str>);
+
+fn input(mut log: ResMut<Log>) {
+    log.0.push("input");
+}
+fn movement(mut log: ResMut<Log>) {
+    log.0.push("movement");
+}
+fn collisions(mut log: ResMut<Log>) {
+    log.0.push("collisions");
+}
+fn physics(mut log: ResMut<Log>) {
+    log.0.push("physics");
+}
+fn draw(mut log: ResMut<Log>) {
+    log.0.push("draw");
+}
+
+fn run(systems: &mut Systems, frame: fn(&mut Systems, &mut World)) -> Vec<&'static str> {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+    frame(systems, &mut world);
+    let log = world.get_resource::<Log>().unwrap();
+    log.0.clone()
+}
+
+#[test]
+fn stages_run_in_order() {
+    let mut systems = Systems::new();
+    systems
+        .add_stage_after(Stage::Update, Stage::Custom("Physics"))
+        .with_system(draw.in_stage(Stage::Render))
+        .with_system(physics.in_stage(Stage::Custom("Physics")))
+        .with_system(collisions.in_stage(Stage::PostUpdate))
+        .with_system(movement)
+        .with_system(input.in_stage(Stage::PreUpdate));
+
+    assert_eq!(
+        run(&mut systems, |systems, world| systems.update(world)),
+        vec!["input", "movement", "physics", "collisions"]
+    );
+    assert_eq!(run(&mut systems, |systems, world| systems.render(world)), vec!["draw"]);
+    assert_eq!(
+        run(&mut systems, |systems, world| systems.run_stage(Stage::Custom("Physics"), world)),
+        vec!["physics"]
+    );
+}
+
+#[test]
+fn constraints_order_systems_inside_stage() {
+    let mut systems = Systems::new();
+    systems
+        .with_system(collisions.label("collisions").after("movement"))
+        .with_system(physics.before("collisions").label("physics").after("movement"))
+        .with_system(movement.label("movement").after("input"))
+        .with_system(input.label("input"));
+    assert!(systems.sort().is_ok());
+
+    assert_eq!(
+        run(&mut systems, |systems, world| systems.update(world)),
+        vec!["input", "movement", "physics", "collisions"]
+    );
+}
+
+#[test]
+fn cycles_name_the_systems() {
+    let mut systems = Systems::new();
+    systems
+        .with_system(draw)
+        .with_system(input.label("input").after("collisions"))
+        .with_system(movement.label("movement").after("input"))
+        .with_system(collisions.label("collisions").after("movement"));
+    let ScheduleError::Cycle(error) = systems.sort().unwrap_err() else {
+        panic!("the cycle was not found");
+    };
+    assert_eq!(error.stage, Stage::Update);
+    assert_eq!(error.systems.len(), 3);
+    let message = error.to_string();
+    assert!(message.starts_with("Systems in stage Update have cyclic ordering constraints"));
+    for name in ["input", "movement", "collisions"] {
+        assert!(message.contains(name));
+    }
+    assert!(!message.contains("draw"));
+}
+
+#[test]
+fn unknown_labels_are_errors() {
+    let mut systems = Systems::new();
+    systems
+        .with_system(input.label("input").in_stage(Stage::PreUpdate))
+        .with_system(movement.label("movement").after("input"))
+        .with_system(collisions.label("collisions").after("movment"));
+    assert_eq!(
+        systems.sort(),
+        Err(ScheduleError::UnknownLabel {
+            stage: Stage::Update,
+            system: "collisions".to_string(),
+            label: "movment"
+        })
+    );
+}
+
+#[test]
+#[should_panic(expected = "cyclic ordering constraints")]
+fn update_panics_on_cycle() {
+    let mut systems = Systems::new();
+    systems
+        .with_system(input.label("input").after("movement"))
+        .with_system(movement.label("movement").after("input"));
+    systems.update(&mut World::new());
+}
+
+#[test]
+fn commands_are_applied_between_stages() {
+    #[derive(Component)]
+    struct Marker;
+
+    fn spawn(mut commands: Commands) {
+        commands.spawn().insert(Marker);
+    }
+    fn count_same_stage(query: Query<&Marker>, mut log: ResMut<Log>) {
+        if query.run().is_empty() {
+            log.0.push("same stage sees nothing");
+        }
+    }
+    fn count_next_stage(query: Query<&Marker>, mut log: ResMut<Log>) {
+        if query.run().len() == 1 {
+            log.0.push("next stage sees the entity");
+        }
+    }
+
+    let mut world = World::new();
+    world.register_component::<Marker>();
+    world.add_resource(Log::default());
+    let mut systems = Systems::new();
+    systems
+        .with_system(spawn.in_stage(Stage::PreUpdate))
+        .with_system(count_same_stage.in_stage(Stage::PreUpdate))
+        .with_system(count_next_stage);
+    systems.update(&mut world);
+
+    let log = world.get_resource::<Log>().unwrap();
+    assert_eq!(log.0, vec!["same stage sees nothing", "next stage sees the entity"]);
+}
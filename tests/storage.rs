@@ -0,0 +1,91 @@
+use std::any::Any;
+
+use wgtr_ecs::*;
+
+#[test]
+fn query_table_and_sparse_components_together() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component_with_storage::<Stunned>(StorageType::SparseSet)?;
+
+    let stunned = world
+        .create_entity()
+        .with_component(Health(100))?
+        .with_component(Stunned(2))?
+        .id();
+    world.create_entity().with_component(Health(50))?;
+    world.create_entity().with_component(Stunned(5))?;
+
+    let query = world
+        .dynamic_query()
+        .with_component::<Health>()?
+        .with_component::<Stunned>()?
+        .run();
+    assert_eq!(query.0, vec![stunned]);
+    let stuns: &Vec<ComponentCell<dyn Any>> = &query.1[1];
+    assert_eq!(stuns[0].borrow().downcast_ref::<Stunned>().unwrap().0, 2);
+
+    let query = world.dynamic_query().with_component::<Stunned>()?.run();
+    assert_eq!(query.0.len(), 2);
+
+    for entity in world.dynamic_query().with_component::<Stunned>()?.run_entity() {
+        let mut stun = entity.get_component_mut::<Stunned>()?;
+        stun.0 -= 1;
+    }
+    for entity in world.dynamic_query().with_component::<Stunned>()?.run_entity() {
+        let stun = entity.get_component::<Stunned>()?;
+        assert!(stun.0 == 1 || stun.0 == 4);
+    }
+    Ok(())
+}
+
+#[test]
+fn toggle_sparse_component() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component_with_storage::<Stunned>(StorageType::SparseSet)?;
+
+    let first = world.create_entity().with_component(Health(100))?.id();
+    let second = world.create_entity().with_component(Health(200))?.id();
+
+    for frame in 0..10 {
+        let target = if frame % 2 == 0 { first } else { second };
+        world.add_component(Stunned(frame), target)?;
+
+        let query = world.dynamic_query().with_component::<Stunned>()?.run();
+        assert_eq!(query.0, vec![target]);
+
+        world.remove_component::<Stunned>(target)?;
+        let query = world.dynamic_query().with_component::<Stunned>()?.run();
+        assert!(query.0.is_empty());
+    }
+
+    let query = world.dynamic_query().with_component::<Health>()?.run();
+    assert_eq!(query.0, vec![first, second]);
+    Ok(())
+}
+
+#[test]
+fn removed_entity_leaves_sparse_set() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component_with_storage::<Stunned>(StorageType::SparseSet)?;
+
+    let removed = world.create_entity().with_component(Stunned(1))?.id();
+    world.remove_entity(removed)?;
+    let reused = world.create_entity().id();
+    assert_eq!(reused.index(), removed.index());
+
+    let query = world.dynamic_query().with_component::<Stunned>()?.run();
+    assert!(query.0.is_empty());
+
+    world.add_component(Stunned(3), reused)?;
+    let query = world.dynamic_query().with_component::<Stunned>()?.run();
+    assert_eq!(query.0, vec![reused]);
+    Ok(())
+}
+
+#[allow(dead_code)]
+#[derive(Component)]
+struct Health(pub u32);
+#[derive(Component)]
+struct Stunned(pub u32);
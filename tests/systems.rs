@@ -1,4 +1,4 @@
-use wgtr_ecs::{System, World, Systems};
+use wgtr_ecs::*;
 
 #[test]
 fn create_and_init_system(){
@@ -6,7 +6,7 @@ fn create_and_init_system(){
 
     impl System for SimpleSystem{
         fn init(&mut self, world : &mut wgtr_ecs::World){
-            let x = world.get_resource_mut::<u32>().unwrap();
+            let mut x = world.get_resource_mut::<u32>().unwrap();
             *x += 1;
         }
     }
@@ -22,3 +22,162 @@ fn create_and_init_system(){
     assert_eq!(*new_x, 12);
 }
 
+
+#[derive(Component)]
+struct Position(i32);
+#[derive(Component)]
+struct Velocity(i32);
+struct Frames(u32);
+struct Hit(Entity);
+
+fn movement(query: Query<(&mut Position, &Velocity)>, mut frames: ResMut<Frames>) {
+    for (mut position, velocity) in query.run() {
+        position.0 += velocity.0;
+    }
+    frames.0 += 1;
+}
+
+fn hit_walls(query: Query<(Entity, &Position)>, mut hits: EventWriter<Hit>) {
+    for (entity, position) in query.run() {
+        if position.0 >= 10 {
+            hits.send(Hit(entity));
+        }
+    }
+}
+
+fn despawn_hit(mut hits: EventReader<Hit>, mut commands: Commands, mut total: Local<u32>, frames: Res<Frames>) {
+    for hit in hits.read() {
+        commands.despawn(hit.0);
+        *total += 1;
+    }
+    assert!(*total <= frames.0);
+}
+
+#[test]
+fn function_systems_fetch_their_parameters() -> Result<(), EcsError> {
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.register_component::<Velocity>();
+    world.add_resource(Frames(0));
+    let slow = world
+        .create_entity()
+        .with_component(Position(0))?
+        .with_component(Velocity(1))?
+        .id();
+    let fast = world
+        .create_entity()
+        .with_component(Position(0))?
+        .with_component(Velocity(5))?
+        .id();
+    let still = world.create_entity().with_component(Position(0))?.id();
+
+    let mut systems = Systems::new();
+    systems
+        .with_system(despawn_hit)
+        .with_system(movement)
+        .with_system(hit_walls);
+    systems.init(&mut world);
+
+    for _ in 0..3 {
+        systems.update(&mut world);
+    }
+    assert_eq!(world.get_resource::<Frames>().unwrap().0, 3);
+    assert!(!world.is_alive(fast));
+    assert!(world.is_alive(slow));
+    assert!(world.is_alive(still));
+    assert_eq!(world.query::<&Position>().get(slow)?.0, 3);
+    Ok(())
+}
+
+#[test]
+fn function_and_struct_systems_mix() {
+    struct Reset;
+    impl System for Reset {
+        fn update(&mut self, world: &mut World) {
+            *world.get_resource_mut::<u32>().unwrap() = 0;
+        }
+    }
+
+    fn count(mut counter: ResMut<u32>, mut calls: Local<u32>) {
+        *calls += 1;
+        *counter += *calls;
+    }
+
+    let mut world = World::new();
+    world.add_resource(0_u32);
+    let mut systems = Systems::new();
+    systems.with_system(count).with_system(count);
+    systems.update(&mut world);
+    systems.update(&mut world);
+    assert_eq!(*world.get_resource::<u32>().unwrap(), 6); // every system has its own Local
+
+    systems.with_system(Reset);
+    systems.update(&mut world);
+    assert_eq!(*world.get_resource::<u32>().unwrap(), 0);
+}
+
+#[derive(Default)]
+struct Seen(Vec<usize>);
+
+fn move_all(query: Query<&mut Position>) {
+    for mut position in query.run() {
+        position.0 += 1;
+    }
+}
+
+fn count_changed(query: Query<&Position, Changed<Position>>, mut seen: ResMut<Seen>) {
+    seen.0.push(query.run().len());
+}
+
+fn count_added(query: Query<&Position, Added<Position>>, mut seen: ResMut<Seen>) {
+    seen.0.push(query.run().len());
+}
+
+#[test]
+fn render_systems_see_changes_of_the_same_frame() {
+    let mut world = World::new();
+    world.add_resource(Seen::default());
+    world.spawn(Position(0));
+    let mut systems = Systems::new();
+    systems
+        .with_system(move_all)
+        .with_system(count_changed.in_stage(Stage::Render));
+    systems.init(&mut world);
+
+    for _ in 0..3 {
+        systems.update(&mut world);
+        systems.render(&mut world);
+    }
+    assert_eq!(world.get_resource::<Seen>().unwrap().0, [1, 1, 1]);
+}
+
+#[test]
+fn render_does_not_hide_changes_from_the_next_update() {
+    let mut world = World::new();
+    world.add_resource(Seen::default());
+    world.spawn(Position(0));
+    let mut systems = Systems::new();
+    systems
+        .with_system(count_changed)
+        .with_system(move_all.in_stage(Stage::PostUpdate));
+    systems.init(&mut world);
+
+    for _ in 0..3 {
+        systems.update(&mut world);
+        systems.render(&mut world);
+    }
+    assert_eq!(world.get_resource::<Seen>().unwrap().0, [1, 1, 1]);
+}
+
+#[test]
+fn entities_spawned_before_init_are_added() {
+    let mut world = World::new();
+    world.add_resource(Seen::default());
+    world.spawn(Position(0));
+    let mut systems = Systems::new();
+    systems.with_system(count_added);
+    systems.init(&mut world);
+
+    systems.update(&mut world);
+    assert_eq!(world.get_resource::<Seen>().unwrap().0, [1]);
+}
@@ -0,0 +1,149 @@
+//! Derive macros re-exported by `wgtr-ecs`, use them through `wgtr_ecs::*`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Index, LitStr, Path};
+
+/// Implements `wgtr_ecs::Component` for the type. It is configured with the `component` attribute:
+///
+/// - `storage = "table"` or `storage = "sparse_set"`, see `wgtr_ecs::StorageType`
+/// - `on_insert = path::to::function` and `on_remove = path::to::function`, hooks taking `(&mut World, Entity)`
+/// - `name = "..."`, the serialization name, defaults to `Component::serialization_name()`, the type name with its module path
+///
+/// ```ignore
+/// #[derive(Component)]
+/// #[component(storage = "sparse_set", on_remove = drop_target, name = "target")]
+/// struct Target(Entity);
+/// ```
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    component(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn component(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut storage = None;
+    let mut on_insert = None;
+    let mut on_remove = None;
+    let mut name = None;
+
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attribute| attribute.path().is_ident("component"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("storage") {
+                let value: LitStr = meta.value()?.parse()?;
+                storage = Some(match value.value().as_str() {
+                    "table" => quote!(Table),
+                    "sparse_set" => quote!(SparseSet),
+                    _ => return Err(syn::Error::new(value.span(), "expected \"table\" or \"sparse_set\"")),
+                });
+            } else if meta.path.is_ident("on_insert") {
+                on_insert = Some(meta.value()?.parse::<Path>()?);
+            } else if meta.path.is_ident("on_remove") {
+                on_remove = Some(meta.value()?.parse::<Path>()?);
+            } else if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("expected `storage`, `on_insert`, `on_remove` or `name`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let storage = storage.map(|storage| {
+        quote! {
+            const STORAGE: ::wgtr_ecs::StorageType = ::wgtr_ecs::StorageType::#storage;
+        }
+    });
+    let on_insert = on_insert.map(|hook| {
+        quote! {
+            fn on_insert(world: &mut ::wgtr_ecs::World, entity: ::wgtr_ecs::Entity) {
+                #hook(world, entity)
+            }
+        }
+    });
+    let name = name.map(|name| {
+        quote! {
+            fn serialization_name() -> &'static str {
+                #name
+            }
+        }
+    });
+    let on_remove = on_remove.map(|hook| {
+        quote! {
+            fn on_remove(world: &mut ::wgtr_ecs::World, entity: ::wgtr_ecs::Entity) {
+                #hook(world, entity)
+            }
+        }
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::wgtr_ecs::Component for #ident #type_generics #where_clause {
+            #storage
+            #on_insert
+            #on_remove
+            #name
+        }
+    })
+}
+
+/// Implements `wgtr_ecs::Bundle` for a struct whose fields are all components or bundles.
+///
+/// ```ignore
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     health: Health,
+///     speed: Speed,
+/// }
+/// ```
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bundle(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn bundle(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "Bundle can only be derived for structs",
+        ));
+    };
+    let types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+    let members: Vec<_> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(index);
+                quote!(#index)
+            }
+        })
+        .collect();
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::wgtr_ecs::Bundle for #ident #type_generics #where_clause {
+            fn register_components(world: &mut ::wgtr_ecs::World, component_ids: &mut ::std::vec::Vec<usize>) {
+                #(<#types as ::wgtr_ecs::Bundle>::register_components(world, component_ids);)*
+            }
+
+            fn registered_components(world: &::wgtr_ecs::World, component_ids: &mut ::std::vec::Vec<usize>) {
+                #(<#types as ::wgtr_ecs::Bundle>::registered_components(world, component_ids);)*
+            }
+
+            fn write(self, writer: &mut ::wgtr_ecs::BundleWriter<'_>) {
+                #(::wgtr_ecs::Bundle::write(self.#members, writer);)*
+            }
+        }
+    })
+}